@@ -14,7 +14,7 @@ use Proxmox::Install::Config;
 use Proxmox::Install::StorageConfig;
 
 use Proxmox::Sys::Block qw(get_cached_disks wipe_disk partition_bootable_disk);
-use Proxmox::Sys::Command qw(run_command syscmd);
+use Proxmox::Sys::Command qw(run_command run_command_secret syscmd);
 use Proxmox::Sys::File qw(file_read_firstline file_read_all file_write_all);
 use Proxmox::Sys::ZFS;
 use Proxmox::UI;
@@ -215,7 +215,22 @@ sub zfs_create_rpool {
     my $cmd = "zpool create -f -o cachefile=none";
     $cmd .= " -o ashift=$zfs_opts->{ashift}" if defined($zfs_opts->{ashift});
 
-    syscmd("$cmd $pool_name $vdev") == 0 || die "unable to create zfs root pool\n";
+    if (my $encryption = $zfs_opts->{encryption}) {
+	my $passphrase = $encryption->{passphrase};
+	die "no passphrase set for zfs encryption\n" if !defined($passphrase) || !length($passphrase);
+
+	my $algorithm = $encryption->{algorithm} // 'aes-256-gcm';
+	die "invalid zfs encryption algorithm '$algorithm'\n"
+	    if $algorithm !~ m/^aes-(?:128|192|256)-(?:ccm|gcm)$/;
+
+	# with stdin not being a terminal, the passphrase is read only once
+	$cmd .= " -O encryption=$algorithm -O keyformat=passphrase -O keylocation=prompt";
+	my $octets = encode("utf-8", $passphrase);
+	run_command_secret("$cmd $pool_name $vdev", undef, $octets, 1) == 0 ||
+	    die "unable to create zfs root pool\n";
+    } else {
+	syscmd("$cmd $pool_name $vdev") == 0 || die "unable to create zfs root pool\n";
+    }
 
     syscmd("zfs create $pool_name/ROOT")  == 0 || die "unable to create zfs $pool_name/ROOT volume\n";
 
@@ -243,6 +258,24 @@ sub zfs_create_rpool {
     syscmd("zfs set acltype=posix $pool_name/ROOT/$root_volume_name");
 }
 
+# Creates a zvol suitable for swapping onto, following the OpenZFS recommendations, and returns its
+# device path.
+sub zfs_create_swap_zvol {
+    my ($pool_name, $size) = @_; # size in GiB
+
+    my $size_kb = int($size * 1024 * 1024) & ~0xFFF; # align to 4 MB
+    die "ZFS swap size must be at least 4 MiB, or 0 to disable swap\n" if $size_kb < 4096;
+
+    my $pagesize = POSIX::sysconf(POSIX::_SC_PAGESIZE()) // 4096;
+
+    syscmd("zfs create -V ${size_kb}K -b $pagesize -o compression=zle -o logbias=throughput"
+	. " -o sync=always -o primarycache=metadata -o secondarycache=none"
+	. " -o com.sun:auto-snapshot=false $pool_name/swap") == 0 ||
+	die "unable to create zfs $pool_name/swap volume\n";
+
+    return "/dev/zvol/$pool_name/swap";
+}
+
 my $get_raid_devlist = sub {
 
     my $dev_name_hash = {};
@@ -252,7 +285,10 @@ my $get_raid_devlist = sub {
     for (my $i = 0; $i < @$cached_disks; $i++) {
 	my $disk_id = Proxmox::Install::Config::get_disk_selection($i) // next;
 
-	my $hd = $cached_disks->[$disk_id];
+	# either the index of the disk or its (stable) device path
+	my $hd = $disk_id =~ m|^/dev/|
+	    ? Proxmox::Sys::Block::find_cached_disk_by_devname($disk_id)
+	    : $cached_disks->[$disk_id];
 	my ($disk, $devname, $size, $model, $logical_bsize) = @$hd;
 	die "device '$devname' is used more than once\n" if $dev_name_hash->{$devname};
 	$dev_name_hash->{$devname} = $hd;
@@ -325,6 +361,24 @@ sub get_zfs_raid_setup {
 	    legacy_bios_4k_check(@$hd[4]);
 	    $cmd .= " @$hd[1]";
 	}
+    } elsif ($filesys =~ m/^zfs \(dRAID-([123])\)$/) {
+	my $level = $1;
+	my $mindisks = 2 + $level;
+	die "zfs (dRAID-$level) needs at least $mindisks devices\n" if scalar(@$devlist) < $mindisks;
+
+	# e.g. 'draid2:8d:2s', without one ZFS chooses the number of data disks and uses no spares
+	my $spec = Proxmox::Install::Config::get_zfs_opt('draid_spec') // "draid$level";
+	die "invalid dRAID specification '$spec' for zfs (dRAID-$level)\n"
+	    if $spec !~ m/^draid$level(?::\d+d)?(?::\d+s)?$/;
+
+	my $hd = @$devlist[0];
+	my $expected_size = @$hd[2]; # all disks need approximately same size
+	$cmd .= " $spec";
+	foreach my $hd (@$devlist) {
+	    zfs_mirror_size_check($expected_size, @$hd[2]);
+	    legacy_bios_4k_check(@$hd[4]);
+	    $cmd .= " @$hd[1]";
+	}
     } else {
 	die "unknown zfs mode '$filesys'\n";
     }
@@ -511,7 +565,14 @@ sub create_lvm_volumes {
 
 	$rest = int($rest - $minfree) & ~0xFFF; # align down to 4 MB boundaries
 
-	if (defined(my $maxvz = Proxmox::Install::Config::get_maxvz())) {
+	my $maxvz = Proxmox::Install::Config::get_maxvz();
+	if (defined($maxvz) && $maxvz == 0 && !Proxmox::Install::Config::get_maxroot()) {
+	    # no data volume wanted and root not limited explicitly, so give it all the space
+	    $rootsize += $rest;
+	    $rest = 0;
+	}
+
+	if (defined($maxvz)) {
 	    $rest = $maxvz * 1024 * 1024 <= $rest ? $maxvz * 1024 * 1024 : $rest;
 	}
 
@@ -571,6 +632,65 @@ sub create_lvm_volumes {
     return ($rootdev, $swapfile, $datadev);
 }
 
+# Formats $dev as LUKS2 container and opens it with the configured passphrase. Returns the path of
+# the unlocked device mapper device and the crypttab entry for the target system.
+my sub setup_luks_device {
+    my ($dev, $encryption) = @_;
+
+    die "cryptsetup not available, cannot set up disk encryption\n" if ! -x '/sbin/cryptsetup';
+
+    my $passphrase = $encryption->{passphrase};
+    die "no passphrase set for disk encryption\n" if !defined($passphrase) || !length($passphrase);
+    my $octets = encode("utf-8", $passphrase);
+
+    my ($name) = $dev =~ m|([^/]+)$|;
+    $name .= '_crypt';
+
+    run_command_secret(
+	['/sbin/cryptsetup', 'luksFormat', '--batch-mode', '--type', 'luks2', '--key-file', '-', $dev],
+	undef,
+	$octets,
+    );
+    run_command_secret(['/sbin/cryptsetup', 'open', '--key-file', '-', $dev, $name], undef, $octets);
+
+    my $uuid = run_command(['/sbin/cryptsetup', 'luksUUID', $dev]);
+    chomp $uuid;
+    die "unable to get LUKS UUID of '$dev'\n" if !$uuid;
+
+    my $options = 'luks,discard';
+    $options .= ',tpm2-device=auto' if $encryption->{tpm_unlock};
+
+    return ("/dev/mapper/$name", "$name UUID=$uuid none $options\n", $name);
+}
+
+# Returns whether the cryptroot initramfs hook will be available in the target, either as part of
+# the base system or from the package pool. Without it, an encrypted root cannot be unlocked on boot.
+my sub has_cryptsetup_initramfs {
+    my ($basefile, $pkgdir) = @_;
+
+    my @debs = glob("${pkgdir}/cryptsetup-initramfs_*.deb");
+    return 1 if scalar(@debs);
+
+    my $hook = 'usr/share/initramfs-tools/hooks/cryptroot';
+    my $listing = eval { run_command(['unsquashfs', '-l', $basefile, $hook], undef, undef, 0, 1) };
+    return defined($listing) && $listing =~ m|/\Q$hook\E$|m;
+}
+
+# Adds a TPM2-bound key slot to the LUKS container, the passphrase stays usable as fallback.
+my sub enroll_luks_tpm2 {
+    my ($targetdir, $dev, $encryption) = @_;
+
+    die "systemd-cryptenroll not available in target, cannot enroll TPM2 key\n"
+	if ! -x "$targetdir/usr/bin/systemd-cryptenroll";
+
+    my $octets = encode("utf-8", $encryption->{passphrase});
+    run_command_secret(
+	"chroot $targetdir /usr/bin/systemd-cryptenroll --tpm2-device=auto --unlock-key-file=/dev/stdin $dev",
+	undef,
+	$octets,
+    );
+}
+
 sub compute_swapsize {
     my ($hdsize) = @_;
 
@@ -588,6 +708,7 @@ sub compute_swapsize {
 	$ss = 512 if $ss < 512;
 	$ss = int($hdgb * 128) if $ss > $hdgb * 128;
 	$ss = 8192 if $ss > 8192;
+	$ss = 4 if $ss < 4; # a swap size of 0 means no swap, so never default to it
 	$swapsize_kb = int($ss * 1024) & ~0xFFF; # align to 4 MB to avoid all to odd SWAP size
     }
 
@@ -677,10 +798,10 @@ my sub setup_root_password {
 
     if (defined($plain)) {
 	my $octets = encode("utf-8", $plain);
-	run_command("chroot $targetdir /usr/sbin/chpasswd", undef, "root:$octets\n");
+	run_command_secret("chroot $targetdir /usr/sbin/chpasswd", undef, "root:$octets\n");
     } elsif (defined($hashed)) {
 	my $octets = encode("utf-8", $hashed);
-	run_command("chroot $targetdir /usr/sbin/chpasswd --encrypted", undef, "root:$octets\n");
+	run_command_secret("chroot $targetdir /usr/sbin/chpasswd --encrypted", undef, "root:$octets\n");
     }
 }
 
@@ -753,9 +874,14 @@ sub extract_data {
 
     my ($swapfile, $rootdev, $datadev);
     my ($use_zfs, $use_btrfs) = (0, 0);
+    my ($luks_dev, $luks_name, $crypttab);
 
     my $filesys = Proxmox::Install::Config::get_filesys();
     my $hdsize = Proxmox::Install::Config::get_hdsize();
+    # only meaningful when booted via UEFI, otherwise the default size is used
+    my $esp_size = $run_env->{boot_type} eq 'efi'
+	? Proxmox::Install::Config::get_esp_size()
+	: undef;
 
     my $zfs_pool_name = Proxmox::Install::StorageConfig::get_zfs_pool_name();
     my $zfs_root_volume_name = Proxmox::Install::StorageConfig::get_zfs_root_volume_name();
@@ -769,6 +895,20 @@ sub extract_data {
 	$use_btrfs = 1;
     }
 
+    # LUKS encryption is only supported for LVM setups
+    my $lvm_encryption = !$use_zfs && !$use_btrfs
+	? Proxmox::Install::Config::get_lvm_encryption()
+	: undef;
+
+    # check the encryption prerequisites before touching any disk, not only once the base system
+    # got extracted at the very end
+    if ($lvm_encryption && !is_test_mode()) {
+	die "cryptsetup-initramfs not available, cannot unlock encrypted root on boot\n"
+	    if !has_cryptsetup_initramfs($basefile, $proxmox_pkgdir);
+	die "no TPM2 device found, cannot enroll disk encryption key\n"
+	    if $lvm_encryption->{tpm_unlock} && ! -e '/dev/tpmrm0';
+    }
+
     if ($use_zfs) {
 	my $i;
 	for ($i = 5; $i > 0; $i--) {
@@ -833,7 +973,7 @@ sub extract_data {
 		my $devname = @$hd[1];
 		my $logical_bsize = @$hd[4];
 
-		my ($size, $osdev, $efidev) = partition_bootable_disk($devname, $hdsize, '8300');
+		my ($size, $osdev, $efidev) = partition_bootable_disk($devname, $hdsize, '8300', $esp_size);
 		$rootdev = $osdev if !defined($rootdev); # simply point to first disk
 		my $by_id = Proxmox::Sys::Block::get_disk_by_id_path($devname);
 		push @$bootdevinfo, {
@@ -868,7 +1008,7 @@ sub extract_data {
 		my $devname = @$hd[1];
 		my $logical_bsize = @$hd[4];
 
-		my ($size, $osdev, $efidev) = partition_bootable_disk($devname, $hdsize, 'BF01');
+		my ($size, $osdev, $efidev) = partition_bootable_disk($devname, $hdsize, 'BF01', $esp_size);
 
 		push @$bootdevinfo, {
 		    esp => $efidev,
@@ -900,8 +1040,16 @@ sub extract_data {
 
 	    zfs_create_rpool($vdev, $zfs_pool_name, $zfs_root_volume_name);
 
+	    if (my $swap_size = Proxmox::Install::Config::get_zfs_opt('swap_size')) {
+		update_progress(0, 0.035, $maxper, "create swap zvol");
+		$swapfile = zfs_create_swap_zvol($zfs_pool_name, $swap_size);
+		Proxmox::Sys::Block::udevadm_trigger_block(1);
+	    }
+
 	} else {
+	    # resolve stable /dev/disk/by-id/ paths, the partitioning code needs the kernel name
 	    my $target_hd = Proxmox::Install::Config::get_target_hd();
+	    $target_hd = Proxmox::Sys::Block::find_cached_disk_by_devname($target_hd)->[1];
 	    die "target '$target_hd' is not a valid block device\n" if ! -b $target_hd;
 	    $diskcount = 1;
 
@@ -911,7 +1059,7 @@ sub extract_data {
 
 	    my $logical_bsize = Proxmox::Sys::Block::logical_blocksize($target_hd);
 
-	    my ($os_size, $osdev, $efidev) = partition_bootable_disk($target_hd, $hdsize, '8E00');
+	    my ($os_size, $osdev, $efidev) = partition_bootable_disk($target_hd, $hdsize, '8E00', $esp_size);
 
 	    Proxmox::Sys::Block::udevadm_trigger_block();
 
@@ -924,11 +1072,18 @@ sub extract_data {
 		logical_bsize => $logical_bsize,
 	    };
 
+	    my $lvmdev = $osdev;
+	    if ($lvm_encryption) {
+		update_progress(0, 0.025, $maxper, "set up disk encryption");
+		$luks_dev = $osdev;
+		($lvmdev, $crypttab, $luks_name) = setup_luks_device($osdev, $lvm_encryption);
+	    }
+
 	    update_progress(0, 0.03, $maxper, "create LVs");
 
 	    my $swap_size = compute_swapsize($os_size);
 	    ($rootdev, $swapfile, $datadev) =
-		create_lvm_volumes($osdev, $os_size, $swap_size);
+		create_lvm_volumes($lvmdev, $os_size, $swap_size);
 
 	    # trigger udev to create /dev/disk/by-uuid
 	    Proxmox::Sys::Block::udevadm_trigger_block(1);
@@ -1069,10 +1224,32 @@ sub extract_data {
 	my $ntype = $ip_version == 4 ? 'inet' : 'inet6';
 
 	my $ethdev = Proxmox::Install::Config::get_mngmt_nic();
+	my $vlan = Proxmox::Install::Config::get_mngmt_vlan();
 	my $cidr = Proxmox::Install::Config::get_cidr();
 	my $gateway = Proxmox::Install::Config::get_gateway();
 
-	if ($iso_env->{cfg}->{bridged_network}) {
+	# interface the host address is configured on, which also gets any dual-stack address
+	my $addr_iface;
+
+	if ($iso_env->{cfg}->{bridged_network} && $vlan) {
+	    # make the bridge VLAN aware, so that guests can use other tags on it too
+	    $ifaces .= "iface $ethdev $ntype manual\n";
+
+	    $addr_iface = "vmbr0.$vlan";
+	    $ifaces .=
+		"\nauto vmbr0.$vlan\niface vmbr0.$vlan $ntype static\n" .
+		"\taddress $cidr\n" .
+		"\tgateway $gateway\n";
+
+	    $ifaces .=
+		"\nauto vmbr0\niface vmbr0 $ntype manual\n" .
+		"\tbridge-ports $ethdev\n" .
+		"\tbridge-stp off\n" .
+		"\tbridge-fd 0\n" .
+		"\tbridge-vlan-aware yes\n" .
+		"\tbridge-vids 2-4094\n";
+	} elsif ($iso_env->{cfg}->{bridged_network}) {
+	    $addr_iface = 'vmbr0';
 	    $ifaces .= "iface $ethdev $ntype manual\n";
 
 	    $ifaces .=
@@ -1082,13 +1259,29 @@ sub extract_data {
 		"\tbridge-ports $ethdev\n" .
 		"\tbridge-stp off\n" .
 		"\tbridge-fd 0\n";
+	} elsif ($vlan) {
+	    $addr_iface = "$ethdev.$vlan";
+	    $ifaces .= "iface $ethdev $ntype manual\n\n" .
+		"auto $ethdev.$vlan\n" .
+		"iface $ethdev.$vlan $ntype static\n" .
+		"\taddress $cidr\n" .
+		"\tgateway $gateway\n";
 	} else {
+	    $addr_iface = $ethdev;
 	    $ifaces .= "auto $ethdev\n" .
 		"iface $ethdev $ntype static\n" .
 		"\taddress $cidr\n" .
 		"\tgateway $gateway\n";
 	}
 
+	if (my $dual_stack_cidr = Proxmox::Install::Config::get_dual_stack_cidr()) {
+	    my $dual_stack_gateway = Proxmox::Install::Config::get_dual_stack_gateway();
+	    my $dual_stack_ntype = $ntype eq 'inet' ? 'inet6' : 'inet';
+	    $ifaces .= "\niface $addr_iface $dual_stack_ntype static\n" .
+		"\taddress $dual_stack_cidr\n" .
+		"\tgateway $dual_stack_gateway\n";
+	}
+
 	my $ipconf = $run_env->{ipconf};
 	foreach my $iface (sort keys %{$ipconf->{ifaces}}) {
 	    my $name = $ipconf->{ifaces}->{$iface}->{name};
@@ -1104,7 +1297,11 @@ sub extract_data {
 	# configure dns
 
 	my $dnsserver = Proxmox::Install::Config::get_dns();
-	my $resolvconf = "search $domain\nnameserver $dnsserver\n";
+	my $search_domain = Proxmox::Install::Config::get_search_domain() // $domain;
+	my $resolvconf = "search $search_domain\nnameserver $dnsserver\n";
+	for my $server ((Proxmox::Install::Config::get_secondary_dns() // [])->@*) {
+	    $resolvconf .= "nameserver $server\n";
+	}
 	file_write_all("$targetdir/etc/resolv.conf", $resolvconf);
 
 	# configure fstab
@@ -1167,6 +1364,7 @@ sub extract_data {
 	$fstab .= "proc /proc proc defaults 0 0\n";
 
 	file_write_all("$targetdir/etc/fstab", $fstab);
+	file_write_all("$targetdir/etc/crypttab", $crypttab) if $crypttab;
 	file_write_all("$targetdir/etc/mtab", "");
 
 	syscmd("cp ${proxmox_libdir}/policy-disable-rc.d $targetdir/usr/sbin/policy-rc.d") == 0 ||
@@ -1269,6 +1467,19 @@ _EOD
 	symlink ("/usr/share/zoneinfo/$timezone", "$targetdir/etc/localtime");
 	file_write_all("$targetdir/etc/timezone", "$timezone\n");
 
+	# set up time synchronization, chrony picks up additional sources from sources.d
+	my $chrony_conf = "$targetdir/etc/chrony/chrony.conf";
+	if (-e $chrony_conf) {
+	    my $ntp_servers = Proxmox::Install::Config::get_ntp_servers() // [];
+	    if (scalar(@$ntp_servers)) {
+		my $sources = join('', map { "server $_ iburst\n" } @$ntp_servers);
+		file_write_all("$targetdir/etc/chrony/sources.d/proxmox-installer.sources", $sources);
+	    }
+	    if (!Proxmox::Install::Config::get_use_dhcp_ntp()) {
+		syscmd(['sed', '-i', 's|^sourcedir /run/chrony-dhcp|#&|', $chrony_conf]);
+	    }
+	}
+
 	# set apt mirror
 	if (my $mirror = $iso_env->{locales}->{country}->{$country}->{mirror}) {
 	    my $fn = "$targetdir/etc/apt/sources.list";
@@ -1279,6 +1490,16 @@ _EOD
 	# file does not exist)
 	file_write_all("$targetdir/var/lib/apt/extended_states", '');
 
+	my $http_proxy = Proxmox::Install::Config::get_http_proxy();
+	if (defined($http_proxy)) {
+	    # same file as written by pve-manager for the datacenter-wide proxy setting
+	    file_write_all(
+		"$targetdir/etc/apt/apt.conf.d/76pveproxy",
+		"Acquire::http::Proxy \"$http_proxy\";\nAcquire::https::Proxy \"$http_proxy\";\n",
+	    );
+	    chmod(0600, "$targetdir/etc/apt/apt.conf.d/76pveproxy");
+	}
+
 	# allow ssh root login
 	syscmd(['sed', '-i', 's/^#\?PermitRootLogin.*/PermitRootLogin yes/', "$targetdir/etc/ssh/sshd_config"]);
 
@@ -1350,6 +1571,9 @@ _EOD
 	    symlink ("/proc/mounts", "$targetdir/etc/mtab");
 	    syscmd("mount -n --bind /dev $targetdir/dev");
 
+	    enroll_luks_tpm2($targetdir, $luks_dev, $lvm_encryption)
+		if $luks_dev && $lvm_encryption->{tpm_unlock};
+
 	    my $bootloader_err_list = [];
 	    eval {
 		syscmd("chroot $targetdir /usr/sbin/update-initramfs -c -k $kapi") == 0 ||
@@ -1412,7 +1636,9 @@ _EOD
 	my $mailto = Proxmox::Install::Config::get_mailto();
 	if ($iso_env->{product} eq 'pmg') {
 	    # save admin email
-	    file_write_all("$targetdir/etc/pmg/pmg.conf", "section: admin\n\temail ${mailto}\n");
+	    my $pmg_conf = "section: admin\n\temail ${mailto}\n";
+	    $pmg_conf .= "\thttp_proxy ${http_proxy}\n" if defined($http_proxy);
+	    file_write_all("$targetdir/etc/pmg/pmg.conf", $pmg_conf);
 
 	} elsif ($iso_env->{product} eq 'pve') {
 
@@ -1423,7 +1649,9 @@ _EOD
 
 	    # write vnc keymap to datacenter.cfg
 	    my $vnckmap = $iso_env->{locales}->{kmap}->{$keymap}->{kvm} || 'en-us';
-	    file_write_all("$tmpdir/datacenter.cfg", "keyboard: $vnckmap\n");
+	    my $datacenter_cfg = "keyboard: $vnckmap\n";
+	    $datacenter_cfg .= "http_proxy: $http_proxy\n" if defined($http_proxy);
+	    file_write_all("$tmpdir/datacenter.cfg", $datacenter_cfg);
 
 	    # save admin email
 	    file_write_all("$tmpdir/user.cfg", "user:root\@pam:1:0:::${mailto}::\n");
@@ -1501,6 +1729,12 @@ _EOD
 	syscmd("umount -d $targetdir");
     }
 
+    if ($luks_name) {
+	my $vgname = $iso_env->{product};
+	syscmd("/sbin/vgchange -an $vgname");
+	syscmd("/sbin/cryptsetup close $luks_name");
+    }
+
     if (!$err && $use_zfs) {
 	syscmd("zfs set sync=standard $zfs_pool_name") == 0 ||
 	    die "unable to set zfs properties\n";
@@ -71,6 +71,7 @@ my sub init_cfg {
 	# disk and filesystem related
 	filesys => 'ext4',
 	hdsize => undef,
+	esp_size => undef, # in MiB, only used when booted via UEFI
 	swapsize => undef,
 	maxroot => undef,
 	minfree => undef,
@@ -81,6 +82,9 @@ my sub init_cfg {
 	    checksum => 'on',
 	    copies => 1,
 	    arc_max => Proxmox::Install::RunEnv::default_zfs_arc_max(), # in MiB
+	    draid_spec => undef, # e.g. 'draid2:8d:2s', only used for dRAID setups
+	    swap_size => undef, # in GiB, no swap zvol is created if unset or 0
+	    encryption => undef, # { algorithm => 'aes-256-gcm', passphrase => '...' }
 	},
 	btrfs_opts => {
 	    compress => 'off',
@@ -89,6 +93,7 @@ my sub init_cfg {
 	target_hd => undef,
 	disk_selection => {},
 	existing_storage_auto_rename => 0,
+	lvm_encryption => undef, # { passphrase => '...', tpm_unlock => 0 }
 
 	# locale
 	country => $country,
@@ -102,6 +107,7 @@ my sub init_cfg {
 
 	# network related
 	mngmt_nic => undef,
+	mngmt_vlan => undef,
 	# FIXME: fix call sites and remove below, it's just an ugly relict of GTK GUI and time
 	# pressure on creating the single source of truth for installation config
 	mngmt_nic_id => undef,
@@ -109,9 +115,22 @@ my sub init_cfg {
 	domain => undef,
 	cidr => undef,
 	gateway => undef,
+	# optional address and gateway of the other address family, for dual-stack setups
+	dual_stack_cidr => undef,
+	dual_stack_gateway => undef,
 	dns => undef,
+	secondary_dns => [],
+	# falls back to the domain if not set
+	search_domain => undef,
 	target_cmdline => undef,
 
+	# time synchronization
+	ntp_servers => [], # in addition to the default pool
+	use_dhcp_ntp => 1,
+
+	# full URL of the HTTP proxy used by the installed system, including any credentials
+	http_proxy => undef,
+
 	# proxmox-first-boot setup
 	first_boot => {
 	    enabled => 0,
@@ -147,6 +166,26 @@ sub get {
     return defined($k) ? $_cfg->{$k} : $_cfg;
 }
 
+# Returns a copy of the given config with all secrets replaced, i.e. the plain-text root password
+# and the disk encryption passphrases. Used for anything ending up in logs or on disk.
+sub redact_secrets {
+    my ($cfg) = @_;
+
+    my $redacted = from_json(to_json($cfg)); # deep copy
+
+    if (defined(my $password = $redacted->{root_password})) {
+	$password->{plain} = '<redacted>' if defined($password->{plain});
+    }
+    if (defined(my $encryption = $redacted->{lvm_encryption})) {
+	$encryption->{passphrase} = '<redacted>';
+    }
+    if (defined(my $encryption = $redacted->{zfs_opts}->{encryption})) {
+	$encryption->{passphrase} = '<redacted>';
+    }
+
+    return $redacted;
+}
+
 sub set_key {
     my ($k, $v) = @_;
     my $cfg = get();
@@ -163,6 +202,9 @@ sub get_filesys { return get('filesys'); }
 sub set_hdsize { set_key('hdsize', $_[0]); }
 sub get_hdsize { return get('hdsize'); }
 
+sub set_esp_size { set_key('esp_size', $_[0]); }
+sub get_esp_size { return get('esp_size'); }
+
 sub set_swapsize { set_key('swapsize', $_[0]); }
 sub get_swapsize { return get('swapsize'); }
 
@@ -239,6 +281,9 @@ sub get_root_password {
     return defined($password->{$key}) ? $password->{$key} : undef;
 }
 
+sub set_lvm_encryption { set_key('lvm_encryption', $_[0]); }
+sub get_lvm_encryption { return get('lvm_encryption'); }
+
 sub set_mailto { set_key('mailto', $_[0]); }
 sub get_mailto { return get('mailto'); }
 
@@ -248,6 +293,9 @@ sub get_root_ssh_keys { return get('root_ssh_keys'); }
 sub set_mngmt_nic { set_key('mngmt_nic', $_[0]); }
 sub get_mngmt_nic { return get('mngmt_nic'); }
 
+sub set_mngmt_vlan { set_key('mngmt_vlan', $_[0]); }
+sub get_mngmt_vlan { return get('mngmt_vlan'); }
+
 sub set_mngmt_nic_id { set_key('mngmt_nic_id', $_[0]); }
 sub get_mngmt_nic_id { return get('mngmt_nic_id'); }
 
@@ -278,9 +326,30 @@ sub get_ip_version { # virtual config
 sub set_gateway { set_key('gateway', $_[0]); }
 sub get_gateway { return get('gateway'); }
 
+sub set_dual_stack_cidr { set_key('dual_stack_cidr', $_[0]); }
+sub get_dual_stack_cidr { return get('dual_stack_cidr'); }
+
+sub set_dual_stack_gateway { set_key('dual_stack_gateway', $_[0]); }
+sub get_dual_stack_gateway { return get('dual_stack_gateway'); }
+
 sub set_dns { set_key('dns', $_[0]); }
 sub get_dns { return get('dns'); }
 
+sub set_secondary_dns { set_key('secondary_dns', $_[0]); }
+sub get_secondary_dns { return get('secondary_dns'); }
+
+sub set_search_domain { set_key('search_domain', $_[0]); }
+sub get_search_domain { return get('search_domain'); }
+
+sub set_ntp_servers { set_key('ntp_servers', $_[0]); }
+sub get_ntp_servers { return get('ntp_servers'); }
+
+sub set_use_dhcp_ntp { set_key('use_dhcp_ntp', $_[0]); }
+sub get_use_dhcp_ntp { return get('use_dhcp_ntp'); }
+
+sub set_http_proxy { set_key('http_proxy', $_[0]); }
+sub get_http_proxy { return get('http_proxy'); }
+
 sub set_target_cmdline { set_key('target_cmdline', $_[0]); }
 sub get_target_cmdline { return get('target_cmdline'); }
 
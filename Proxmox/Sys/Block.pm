@@ -3,12 +3,14 @@ package Proxmox::Sys::Block;
 use strict;
 use warnings;
 
+use Cwd ();
 use File::Basename;
 use IO::File;
 use List::Util qw(first);
 
 use Proxmox::Install::ISOEnv;
-use Proxmox::Sys::Command qw(syscmd);
+use Proxmox::Log;
+use Proxmox::Sys::Command qw(run_command syscmd);
 use Proxmox::Sys::File qw(file_read_firstline);
 use Proxmox::Sys::Udev;
 use Proxmox::UI;
@@ -51,26 +53,136 @@ sub get_dev_uuid {
     return basename($by_uuid_path);
 }
 
+my $include_install_medium = 0;
+# allows listing the disk backing the installation medium as target, e.g. for development
+sub set_include_install_medium {
+    my ($include) = @_;
+    $include_install_medium = !!$include;
+}
+
+# returns the name (as found in /sys/block) of the disk the installation medium is mounted from,
+# e.g. 'sdb' for an USB stick or 'sr0' for a CD/DVD drive, or undef if it cannot be determined
+my sub get_install_medium_disk {
+    my $iso_dir = Proxmox::Install::ISOEnv::get('locations')->{iso};
+
+    my $fh = IO::File->new('/proc/mounts', 'r') or return;
+    my $dev;
+    while (defined(my $line = <$fh>)) {
+	my ($source, $target, $fstype) = split(/\s+/, $line);
+	if ($target eq $iso_dir || $fstype eq 'iso9660') {
+	    $dev = $source;
+	    last;
+	}
+    }
+    close($fh);
+    return if !defined($dev) || $dev !~ m|^/dev/|;
+
+    my $name = basename(Cwd::abs_path($dev) // return);
+    my $sys_path = Cwd::abs_path("/sys/class/block/$name") // return;
+
+    # if the medium is mounted from a partition, e.g. for hybrid ISOs, use the parent disk
+    $name = basename(dirname($sys_path)) if -e "$sys_path/partition";
+
+    return $name;
+}
+
+# returns a hash mapping all LVM physical volumes to the name of their volume group
+my sub get_lvm_pv_vgs {
+    my $res = {};
+
+    my $parser = sub {
+	my ($line) = @_;
+	$line =~ s/^\s+//;
+	$line =~ s/\s+$//;
+	my ($pv, $vg) = split(/\s+/, $line);
+	$res->{$pv} = $vg if $pv && $vg;
+	return;
+    };
+    eval { run_command(['pvs', '--noheadings', '-o', 'pv_name,vg_name'], $parser, undef, 1) };
+    warn "could not query LVM physical volumes - $@" if $@;
+
+    return $res;
+}
+
+# Collects what is already stored on a disk, so that users can be warned before it gets wiped.
+#
+# Returns a hash with the partition table type (`partition_table`), the number of partitions
+# (`partitions`) and a list of all signatures found on the disk itself or any partition
+# (`signatures`), each with its `type` as reported by blkid and the `label`, if any. For LVM
+# physical volumes, the label is the name of the volume group.
+my sub get_disk_contents {
+    my ($bd, $dev_path, $props, $lvm_pvs) = @_;
+
+    my $contents = {
+	partition_table => $props->{ID_PART_TABLE_TYPE},
+	partitions => 0,
+	signatures => [],
+    };
+
+    my $add_signature = sub {
+	my ($dev, $props) = @_;
+
+	my $type = $props->{ID_FS_TYPE} or return;
+	my $label = $type eq 'LVM2_member' ? $lvm_pvs->{$dev} : $props->{ID_FS_LABEL};
+
+	push $contents->{signatures}->@*, {
+	    type => $type,
+	    defined($label) && length($label) ? (label => $label) : (),
+	};
+    };
+
+    $add_signature->($dev_path, $props);
+
+    my $name = basename($bd);
+    for my $part (sort <$bd/$name*>) {
+	my $part_props = Proxmox::Sys::Udev::get_udev_properties($part) // next;
+	next if ($part_props->{DEVTYPE} // '') ne 'partition';
+
+	$contents->{partitions}++;
+	$add_signature->($part_props->{DEVNAME} // '/dev/' . basename($part), $part_props);
+    }
+
+    return $contents;
+}
+
+# Returns a list of all disks usable as installation target:
+#
 # [
-#     [ <index>, "/dev/path", size_in_blocks, "model", logical_blocksize, <name as found in /sys/block> ]
+#     [
+#         <index>, "/dev/path", size_in_blocks, "model", logical_blocksize,
+#         <name as found in /sys/block>, "vendor", "serial", "hdd" | "ssd" | "nvme" | "",
+#         physical_blocksize, "/dev/disk/by-id/path" | "", { contents, see get_disk_contents() },
+#         [ "/dev/alias", ... ]
+#     ]
 # ]
+#
+# The aliases are the device paths of all other disks referring to the same physical device, e.g.
+# the paths of a multipath LUN, see dedup_disk_aliases().
 my sub hd_list {
     if (is_test_mode()) {
 	my $disks = Proxmox::Install::ISOEnv::get_test_images();
 
 	my $i = 0;
 	return [
-	    map { [ $i++, $_, int((-s $_)/512), "TESTDISK", 512, "/sys/block/$_"] } $disks->@*
+	    map {
+		my $contents = { partitions => 0, signatures => [] };
+		[ $i++, $_, int((-s $_)/512), "TESTDISK", 512, "/sys/block/$_", '', '', '', 512, '', $contents, [] ]
+	    } $disks->@*
 	];
     }
 
+    my $lvm_pvs = get_lvm_pv_vgs();
+
+    my $install_medium = $include_install_medium ? undef : get_install_medium_disk();
+
     my $res = [];
+    my $devices = {};
     my $count = 0;
     foreach my $bd (</sys/block/*>) {
+	next if defined($install_medium) && basename($bd) eq $install_medium;
 	next if $bd =~ m|^/sys/block/ram\d+$|;
 	next if $bd =~ m|^/sys/block/loop\d+$|;
 	next if $bd =~ m|^/sys/block/md\d+$|;
-	next if $bd =~ m|^/sys/block/dm-.*$|;
 	next if $bd =~ m|^/sys/block/fd\d+$|;
 	next if $bd =~ m|^/sys/block/sr\d+$|;
 
@@ -80,12 +192,16 @@ my sub hd_list {
 	next if $info !~ m/^E: DEVTYPE=disk$/m;
 	next if $info =~ m/^E: ID_CDROM/m;
 	next if $info =~ m/^E: ID_FS_TYPE=iso9660/m;
+	# device-mapper devices are only of interest if they are multipath devices
+	next if $bd =~ m|^/sys/block/dm-| && $info !~ m/^E: DM_UUID=mpath-/m;
 
 	my ($name) = $info =~ m/^N: (\S+)$/m;
 	next if !$name;
 
 	my $dev_path;
-	if ($info =~ m/^E: DEVNAME=(\S+)$/m) {
+	if ($info =~ m/^E: DM_NAME=(\S+)$/m) {
+	    $dev_path = "/dev/mapper/$1";
+	} elsif ($info =~ m/^E: DEVNAME=(\S+)$/m) {
 	    $dev_path = $1;
 	} else {
 	    $dev_path = "/dev/$name";
@@ -96,6 +212,19 @@ my sub hd_list {
 	chomp $size;
 	next if $size !~ m/^\d+$/;
 	$size = int($size);
+	if (!$size) { # e.g. card readers without a card or unconnected nbd devices
+	    log_info("skipping disk $dev_path: no medium present\n");
+	    next;
+	}
+	if ($size < 2048) { # 1 MiB, in 512 byte sectors
+	    log_info("skipping disk $dev_path: too small (" . $size * 512 . " bytes)\n");
+	    next;
+	}
+	my $ro = file_read_firstline("$bd/ro") // '';
+	if ($ro =~ m/^\s*1\s*$/) {
+	    log_info("skipping disk $dev_path: read-only\n");
+	    next;
+	}
 
 	my $model = file_read_firstline("$bd/device/model") || '';
 	$model =~ s/^\s+//;
@@ -104,6 +233,15 @@ my sub hd_list {
 	    $model = substr ($model, 0, 30);
 	}
 
+	my $vendor = file_read_firstline("$bd/device/vendor") || '';
+	$vendor =~ s/^\s+//;
+	$vendor =~ s/\s+$//;
+	# libata reports "ATA" as vendor for all SATA disks, which does not help anyone
+	$vendor = '' if $vendor eq 'ATA';
+
+	my ($serial) = $info =~ m/^E: ID_SERIAL_SHORT=(\S+)$/m;
+	$serial //= '';
+
 	my $logical_bsize = file_read_firstline("$bd/queue/logical_block_size") // '';
 	chomp $logical_bsize;
 	if ($logical_bsize && $logical_bsize =~ m/^\d+$/) {
@@ -112,7 +250,110 @@ my sub hd_list {
 	    $logical_bsize = undef;
 	}
 
-	push @$res, [$count++, $dev_path, $size, $model, $logical_bsize, "/sys/block/$name"];
+	my $physical_bsize = file_read_firstline("$bd/queue/physical_block_size") // '';
+	chomp $physical_bsize;
+	if ($physical_bsize && $physical_bsize =~ m/^\d+$/) {
+	    $physical_bsize = int($physical_bsize);
+	} else {
+	    $physical_bsize = undef;
+	}
+
+	my $kind = '';
+	if ($name =~ m/^nvme/) {
+	    $kind = 'nvme';
+	} else {
+	    my $rotational = file_read_firstline("$bd/queue/rotational") // '';
+	    chomp $rotational;
+	    $kind = 'hdd' if $rotational eq '1';
+	    $kind = 'ssd' if $rotational eq '0';
+	}
+
+	# not all devices have one, e.g. virtio disks without a serial
+	my $by_id_path = get_disk_by_id_path($dev_path) // '';
+
+	my $props = Proxmox::Sys::Udev::parse_udevadm_info($info);
+	my $contents = get_disk_contents($bd, $dev_path, $props, $lvm_pvs);
+
+	push @$res, [
+	    $count++, $dev_path, $size, $model, $logical_bsize, "/sys/block/$name", $vendor, $serial,
+	    $kind, $physical_bsize, $by_id_path, $contents,
+	];
+
+	$devices->{$name} = {
+	    props => $props,
+	    slaves => [ map { basename($_) } <$bd/slaves/*> ],
+	};
+    }
+
+    return dedup_disk_aliases($res, $devices);
+}
+
+# Groups disks which refer to the same physical device, e.g. the paths of a multipath LUN, and
+# keeps only one entry for each, preferring the multipath device itself. The device paths of all
+# other entries for the same device are appended as list of aliases to the kept entry.
+#
+# $disks - disk list as returned by hd_list(), without aliases
+# $devices - hash with the udev properties (`props`) and slave devices (`slaves`) of each disk,
+#   keyed by their name as found in /sys/block
+sub dedup_disk_aliases {
+    my ($disks, $devices) = @_;
+
+    my $alias_of = {};
+
+    # multipath devices claim all their paths
+    for my $disk ($disks->@*) {
+	my $name = basename($disk->[5]);
+	my $dev = $devices->{$name} // next;
+	next if ($dev->{props}->{DM_UUID} // '') !~ m/^mpath-/;
+
+	$alias_of->{$_} //= $name for ($dev->{slaves} // [])->@*;
+    }
+
+    # otherwise, paths to the same device share the WWN. Only fall back to the serial for SCSI
+    # devices, as e.g. NVMe namespaces of the same controller or disks behind cheap USB adapters
+    # might share it too.
+    my $get_id = sub {
+	my ($name) = @_;
+	my $props = $devices->{$name}->{props} // {};
+	my $id = $props->{ID_WWN_WITH_EXTENSION} // $props->{ID_WWN};
+	$id //= $props->{ID_SERIAL} if ($props->{ID_BUS} // '') eq 'scsi';
+	return $id;
+    };
+
+    # paths not (yet) claimed by a multipath device still belong to it
+    my $by_id = {};
+    for my $name (keys $alias_of->%*) {
+	my $id = $get_id->($name) // next;
+	$by_id->{$id} //= $alias_of->{$name};
+    }
+
+    for my $disk ($disks->@*) {
+	my $name = basename($disk->[5]);
+	next if defined($alias_of->{$name});
+
+	my $id = $get_id->($name) // next;
+
+	if (defined(my $canonical = $by_id->{$id})) {
+	    $alias_of->{$name} = $canonical;
+	} else {
+	    $by_id->{$id} = $name;
+	}
+    }
+
+    my $aliases = {};
+    for my $disk ($disks->@*) {
+	my $canonical = $alias_of->{basename($disk->[5])} // next;
+	push $aliases->{$canonical}->@*, $disk->[1];
+    }
+
+    my $res = [];
+    my $count = 0;
+    for my $disk ($disks->@*) {
+	my $name = basename($disk->[5]);
+	next if defined($alias_of->{$name});
+
+	my (undef, @fields) = $disk->@*;
+	push @$res, [ $count++, @fields, $aliases->{$name} // [] ];
     }
 
     return $res;
@@ -127,11 +368,13 @@ sub get_cached_disks {
     return $cached_disks;
 }
 
+# $dev can either be the kernel device path or the stable /dev/disk/by-id/ path of the disk
 sub find_cached_disk_by_devname {
     my ($dev, $noerr) = @_;
 
     my $disks = get_cached_disks();
-    my $record = first { $_->[1] eq $dev } $disks->@*; # ($disk, $devname, $size, $model, $lbsize)
+    # ($disk, $devname, $size, $model, $lbsize, ..., $by_id_path)
+    my $record = first { $_->[1] eq $dev || ($_->[10] && $_->[10] eq $dev) } $disks->@*;
     die "no such disk device '$dev'\n" if !defined($record) && !$noerr;
 
     return $record;
@@ -171,6 +414,9 @@ sub get_partition_dev {
 	return "${dev}$partnum";
     } elsif ($dev =~ m|^/dev/nvme\d+n\d+$|) {
 	return "${dev}p$partnum";
+    } elsif ($dev =~ m|^/dev/mapper/[^/]+$|) {
+	# multipath devices, partitions are created by kpartx
+	return "${dev}-part$partnum";
     } else {
 	die "unable to get device for partition $partnum on device $dev\n";
     }
@@ -215,7 +461,7 @@ sub wipe_disk {
 };
 
 sub partition_bootable_disk {
-    my ($target_dev, $maxhdsizegb, $ptype) = @_;
+    my ($target_dev, $maxhdsizegb, $ptype, $esp_size) = @_;
 
     die "too dangerous" if is_test_mode();
 
@@ -225,7 +471,7 @@ sub partition_bootable_disk {
     my $hdsize = hd_size($target_dev); # size in KB (1024 bytes)
 
     # For bigger disks default to generous ESP size to allow users having multiple kernels/UKI's
-    my $esp_size = $hdsize > 100 * 1024 * 1024 ? 1024 : 512; # MB
+    $esp_size //= $hdsize > 100 * 1024 * 1024 ? 1024 : 512; # MB
     my $esp_end = $esp_size + 1;
 
     my $restricted_hdsize_mb = 0; # 0 ==> end of partition
@@ -16,7 +16,7 @@ use Proxmox::Log;
 use Proxmox::UI;
 
 use base qw(Exporter);
-our @EXPORT_OK = qw(run_command syscmd CMD_FINISHED);
+our @EXPORT_OK = qw(run_command run_command_secret syscmd CMD_FINISHED);
 
 use constant {
     CMD_RESERVED => 1<<0, # reserve 1 as it's often the default return value of closures
@@ -78,7 +78,7 @@ sub syscmd {
 # exit code.
 #
 # If $cmd contains a pipe |, the command will be executed inside a bash shell.
-# If $cmd contains 'chpasswd', the input will be specially quoted for that purpose.
+# The input is logged, unless $noinputlog is set, use run_command_secret() for secrets.
 #
 # Arguments:
 # * $cmd - The command to run, either a single string or array with individual arguments
@@ -88,8 +88,9 @@ sub syscmd {
 # * $input - Stdin contents for the spawned subprocess
 # * $noout - Whether to append any process output to the return value
 # * $noprint - Whether to print any process output to the parents stdout
+# * $noinputlog - Whether to never log or print the input, see run_command_secret()
 sub run_command {
-    my ($cmd, $func, $input, $noout, $noprint) = @_;
+    my ($cmd, $func, $input, $noout, $noprint, $noinputlog) = @_;
 
     my $cmdstr;
     if (!ref($cmd)) {
@@ -105,7 +106,7 @@ sub run_command {
     }
 
     my $cmdtxt;
-    if ($input && ($cmdstr !~ m/chpasswd/)) {
+    if ($input && !$noinputlog) {
 	$cmdtxt = "# $cmdstr <<EOD\n$input";
 	chomp $cmdtxt;
 	$cmdtxt .= "\nEOD\n";
@@ -208,6 +209,14 @@ sub run_command {
     return $ostream;
 }
 
+# Like run_command(), but never logs or prints the input, as it carries secrets like passwords or
+# passphrases. Always use this for commands reading a secret from stdin.
+sub run_command_secret {
+    my ($cmd, $func, $input, $noout, $noprint) = @_;
+
+    return run_command($cmd, $func, $input, $noout, $noprint, 1);
+}
+
 # forks and runs the provided coderef in the child
 # do not use syscmd or run_command as both confuse the GTK mainloop if
 # run from a child process
@@ -19,7 +19,10 @@ use proxmox_auto_installer::{
         verify_locale_settings, AutoInstSettings, FetchAnswerFrom, HttpOptions,
     },
 };
-use proxmox_installer_common::{FIRST_BOOT_EXEC_MAX_SIZE, FIRST_BOOT_EXEC_NAME};
+use proxmox_installer_common::{
+    sysinfo::{disk_skip_reason, probe_disks_with_skipped},
+    FIRST_BOOT_EXEC_MAX_SIZE, FIRST_BOOT_EXEC_NAME,
+};
 
 static PROXMOX_ISO_FLAG: &str = "/auto-installer-capable";
 
@@ -52,6 +55,11 @@ struct CommandDeviceInfo {
     /// For which device type information should be shown
     #[arg(name="type", short, long, value_enum, default_value_t=AllDeviceTypes::All)]
     device: AllDeviceTypes,
+
+    /// Additionally list block devices which are not considered as installation target, along
+    /// with the reason why
+    #[arg(long, default_value_t = false)]
+    list_skipped_disks: bool,
 }
 
 /// Test which devices the given filter matches against
@@ -203,6 +211,8 @@ enum OutputFormat {
 struct Devs {
     disks: Option<BTreeMap<String, BTreeMap<String, String>>>,
     nics: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped_disks: Option<BTreeMap<String, String>>,
 }
 
 fn main() {
@@ -224,6 +234,7 @@ fn info(args: &CommandDeviceInfo) -> Result<()> {
     let mut devs = Devs {
         disks: None,
         nics: None,
+        skipped_disks: None,
     };
 
     if args.device == AllDeviceTypes::Network || args.device == AllDeviceTypes::All {
@@ -238,6 +249,19 @@ fn info(args: &CommandDeviceInfo) -> Result<()> {
             Err(err) => bail!("Error getting disk data: {err}"),
         }
     }
+    if args.list_skipped_disks {
+        match probe_disks_with_skipped() {
+            Ok((_, skipped)) => {
+                devs.skipped_disks = Some(
+                    skipped
+                        .into_iter()
+                        .map(|disk| (disk.name, disk.reason.to_string()))
+                        .collect(),
+                )
+            }
+            Err(err) => bail!("Error probing disks: {err}"),
+        }
+    }
     println!("{}", serde_json::to_string_pretty(&devs).unwrap());
     Ok(())
 }
@@ -466,7 +490,7 @@ fn get_iso_uuid(iso: impl AsRef<Path>) -> Result<String> {
         if line.starts_with("-volume_date uuid") {
             uuid = line
                 .split(' ')
-                .last()
+                .next_back()
                 .ok_or_else(|| format_err!("xorriso did behave unexpectedly"))?
                 .replace('\'', "")
                 .trim()
@@ -503,6 +527,10 @@ fn get_disks() -> Result<BTreeMap<String, BTreeMap<String, String>>> {
             }
         }
 
+        if disk_skip_reason(&entry.path()).is_some() {
+            continue;
+        }
+
         let output = match get_udev_properties(entry.path()) {
             Ok(output) => output,
             Err(err) => {
@@ -2,8 +2,8 @@ use anyhow::{format_err, Result};
 use clap::ValueEnum;
 use proxmox_installer_common::{
     options::{
-        BtrfsCompressOption, BtrfsRaidLevel, FsType, ZfsChecksumOption, ZfsCompressOption,
-        ZfsRaidLevel,
+        BtrfsCompressOption, BtrfsRaidLevel, FsType, KeyboardLayout, LvmSize, ZfsChecksumOption,
+        ZfsCompressOption, ZfsRaidLevel,
     },
     utils::{CidrAddress, Fqdn},
 };
@@ -220,6 +220,9 @@ pub struct DiskSetup {
     pub zfs: Option<ZfsOptions>,
     pub lvm: Option<LvmOptions>,
     pub btrfs: Option<BtrfsOptions>,
+    /// Install even if the selected disks already contain data, e.g. an old installation.
+    #[serde(default)]
+    pub wipe_existing_data: bool,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -229,6 +232,7 @@ pub struct Disks {
     pub disk_selection: DiskSelection,
     pub filter_match: Option<FilterMatch>,
     pub fs_options: FsOptions,
+    pub wipe_existing_data: bool,
 }
 
 impl TryFrom<DiskSetup> for Disks {
@@ -297,6 +301,7 @@ impl TryFrom<DiskSetup> for Disks {
             disk_selection,
             filter_match: source.filter_match,
             fs_options,
+            wipe_existing_data: source.wipe_existing_data,
         };
         Ok(res)
     }
@@ -340,16 +345,19 @@ pub struct ZfsOptions {
     pub compress: Option<ZfsCompressOption>,
     pub copies: Option<usize>,
     pub hdsize: Option<f64>,
+    /// Size of the swap zvol in GiB, none is created if unset or 0.
+    pub swapsize: Option<f64>,
 }
 
 #[derive(Clone, Copy, Default, Deserialize, Serialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct LvmOptions {
     pub hdsize: Option<f64>,
-    pub swapsize: Option<f64>,
-    pub maxroot: Option<f64>,
-    pub maxvz: Option<f64>,
-    pub minfree: Option<f64>,
+    /// The sizes are either given in GiB or as percentage of `hdsize`, e.g. "25%".
+    pub swapsize: Option<LvmSize>,
+    pub maxroot: Option<LvmSize>,
+    pub maxvz: Option<LvmSize>,
+    pub minfree: Option<LvmSize>,
 }
 
 #[derive(Clone, Copy, Default, Deserialize, Debug)]
@@ -359,35 +367,3 @@ pub struct BtrfsOptions {
     pub raid: Option<BtrfsRaidLevel>,
     pub compress: Option<BtrfsCompressOption>,
 }
-
-#[derive(Clone, Deserialize, Serialize, Debug, PartialEq)]
-#[serde(rename_all = "kebab-case", deny_unknown_fields)]
-pub enum KeyboardLayout {
-    De,
-    DeCh,
-    Dk,
-    EnGb,
-    EnUs,
-    Es,
-    Fi,
-    Fr,
-    FrBe,
-    FrCa,
-    FrCh,
-    Hu,
-    Is,
-    It,
-    Jp,
-    Lt,
-    Mk,
-    Nl,
-    No,
-    Pl,
-    Pt,
-    PtBr,
-    Se,
-    Si,
-    Tr,
-}
-
-serde_plain::derive_display_from_serialize!(KeyboardLayout);
@@ -1,15 +1,19 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use clap::ValueEnum;
 use glob::Pattern;
 use log::info;
-use std::{collections::BTreeMap, process::Command};
+use std::{collections::BTreeMap, net::Ipv4Addr, process::Command};
 
 use crate::{
     answer::{self, Answer, FirstBootHookSourceMode},
     udevinfo::UdevInfo,
 };
 use proxmox_installer_common::{
-    options::{email_validate, FsType, NetworkOptions, ZfsChecksumOption, ZfsCompressOption},
+    disk_checks::{check_bootdisk_config, check_disks_existing_data},
+    options::{
+        email_validate, recommended_zfs_ashift, smallest_disk, Disk, FsType, LvmBootdiskOptions,
+        NetworkOptions, ZfsBootdiskOptions, ZfsChecksumOption, ZfsCompressOption,
+    },
     setup::{
         InstallBtrfsOption, InstallConfig, InstallFirstBootSetup, InstallRootPassword,
         InstallZfsOption, LocaleInfo, RuntimeInfo, SetupInfo,
@@ -24,7 +28,18 @@ fn get_network_settings(
     setup_info: &SetupInfo,
 ) -> Result<NetworkOptions> {
     let mut network_options = NetworkOptions::defaults_from(setup_info, &runtime_info.network);
+    apply_network_settings(answer, udev_info, &mut network_options)?;
+    Ok(network_options)
+}
 
+/// Applies the network settings of the answer file on top of the given options, i.e. the FQDN
+/// and, if configured manually, the address, gateway, DNS server and the interface matching the
+/// filter. Everything else, e.g. the settings obtained via DHCP, is kept.
+pub fn apply_network_settings(
+    answer: &Answer,
+    udev_info: &UdevInfo,
+    network_options: &mut NetworkOptions,
+) -> Result<()> {
     info!("Setting network configuration");
 
     // Always use the FQDN from the answer file
@@ -32,12 +47,12 @@ fn get_network_settings(
 
     if let answer::NetworkSettings::Manual(settings) = &answer.network.network_settings {
         network_options.address = settings.cidr.clone();
-        network_options.dns_server = settings.dns;
+        network_options.dns_servers = vec![settings.dns];
         network_options.gateway = settings.gateway;
         network_options.ifname = get_single_udev_index(&settings.filter, &udev_info.nics)?;
     }
     info!("Network interface used is '{}'", &network_options.ifname);
-    Ok(network_options)
+    Ok(())
 }
 
 pub fn get_single_udev_index(
@@ -152,71 +167,41 @@ pub fn get_matched_udev_indexes(
     Ok(matches)
 }
 
-fn set_disks(
+/// Returns the disks selected in the answer file, either by name or by filter. For ext4 and xfs,
+/// a filter selects the first matching disk only. Disks which are not found or listed more than
+/// once are rejected.
+///
+/// # Arguments
+///
+/// * `answer` - Answer file as provided by the user
+/// * `udev_info` - udev information for all system devices
+/// * `runtime_info` - Runtime information, for the available disks
+pub fn get_selected_disks<'a>(
     answer: &Answer,
     udev_info: &UdevInfo,
-    runtime_info: &RuntimeInfo,
-    config: &mut InstallConfig,
-) -> Result<()> {
-    match config.filesys {
-        FsType::Ext4 | FsType::Xfs => set_single_disk(answer, udev_info, runtime_info, config),
-        FsType::Zfs(_) | FsType::Btrfs(_) => {
-            set_selected_disks(answer, udev_info, runtime_info, config)
-        }
-    }
-}
-
-fn set_single_disk(
-    answer: &Answer,
-    udev_info: &UdevInfo,
-    runtime_info: &RuntimeInfo,
-    config: &mut InstallConfig,
-) -> Result<()> {
-    match &answer.disks.disk_selection {
+    runtime_info: &'a RuntimeInfo,
+) -> Result<Vec<&'a Disk>> {
+    let disks: Vec<&Disk> = match &answer.disks.disk_selection {
         answer::DiskSelection::Selection(disk_list) => {
-            let disk_name = disk_list[0].clone();
-            let disk = runtime_info
-                .disks
+            info!("Disk selection found");
+            disk_list
                 .iter()
-                .find(|item| item.path.ends_with(disk_name.as_str()));
-            match disk {
-                Some(disk) => config.target_hd = Some(disk.path.clone()),
-                None => bail!("disk in 'disk_selection' not found"),
-            }
+                .map(|name| {
+                    runtime_info
+                        .disks
+                        .iter()
+                        .find(|item| item.path.ends_with(name.as_str()))
+                        .ok_or_else(|| anyhow!("disk '{name}' in 'disk_list' not found"))
+                })
+                .collect::<Result<_>>()?
         }
-        answer::DiskSelection::Filter(filter) => {
+        answer::DiskSelection::Filter(filter) if answer.disks.fs_type.is_lvm() => {
             let disk_index = get_single_udev_index(filter, &udev_info.disks)?;
-            let disk = runtime_info
+            runtime_info
                 .disks
                 .iter()
-                .find(|item| item.index == disk_index);
-            config.target_hd = disk.map(|d| d.path.clone());
-        }
-    }
-    info!("Selected disk: {}", config.target_hd.clone().unwrap());
-    Ok(())
-}
-
-fn set_selected_disks(
-    answer: &Answer,
-    udev_info: &UdevInfo,
-    runtime_info: &RuntimeInfo,
-    config: &mut InstallConfig,
-) -> Result<()> {
-    match &answer.disks.disk_selection {
-        answer::DiskSelection::Selection(disk_list) => {
-            info!("Disk selection found");
-            for disk_name in disk_list.clone() {
-                let disk = runtime_info
-                    .disks
-                    .iter()
-                    .find(|item| item.path.ends_with(disk_name.as_str()));
-                if let Some(disk) = disk {
-                    config
-                        .disk_selection
-                        .insert(disk.index.clone(), disk.index.clone());
-                }
-            }
+                .filter(|item| item.index == disk_index)
+                .collect()
         }
         answer::DiskSelection::Filter(filter) => {
             info!("No disk list found, looking for disk filters");
@@ -231,41 +216,56 @@ fn set_selected_disks(
                 filter_match == answer::FilterMatch::All,
             )?;
 
-            for i in selected_disk_indexes.into_iter() {
-                let disk = runtime_info
-                    .disks
-                    .iter()
-                    .find(|item| item.index == i)
-                    .unwrap();
+            runtime_info
+                .disks
+                .iter()
+                .filter(|item| selected_disk_indexes.contains(&item.index))
+                .collect()
+        }
+    };
+
+    if disks.is_empty() {
+        bail!("No disks found matching selection.");
+    }
+    if let Some(disk) = disks
+        .iter()
+        .enumerate()
+        .find_map(|(i, disk)| disks[..i].contains(disk).then_some(disk))
+    {
+        bail!("disk {} is selected more than once", disk.path);
+    }
+
+    Ok(disks)
+}
+
+fn set_disks(
+    answer: &Answer,
+    udev_info: &UdevInfo,
+    runtime_info: &RuntimeInfo,
+    config: &mut InstallConfig,
+) -> Result<()> {
+    let disks = get_selected_disks(answer, udev_info, runtime_info)?;
+
+    match config.filesys {
+        FsType::Ext4 | FsType::Xfs => {
+            config.target_hd = Some(disks[0].path.clone());
+            info!("Selected disk: {}", disks[0].path);
+        }
+        FsType::Zfs(_) | FsType::Btrfs(_) => {
+            for disk in &disks {
                 config
                     .disk_selection
                     .insert(disk.index.clone(), disk.index.clone());
             }
+            info!(
+                "Selected disks: {}",
+                disks
+                    .iter()
+                    .map(|d| d.path.to_string() + " ")
+                    .collect::<String>()
+            );
         }
     }
-    if config.disk_selection.is_empty() {
-        bail!("No disks found matching selection.");
-    }
-
-    let mut selected_disks: Vec<String> = Vec::new();
-    for i in config.disk_selection.keys() {
-        selected_disks.push(
-            runtime_info
-                .disks
-                .iter()
-                .find(|item| item.index.as_str() == i)
-                .unwrap()
-                .clone()
-                .path,
-        );
-    }
-    info!(
-        "Selected disks: {}",
-        selected_disks
-            .iter()
-            .map(|x| x.to_string() + " ")
-            .collect::<String>()
-    );
 
     Ok(())
 }
@@ -281,7 +281,55 @@ fn get_first_selected_disk(config: &InstallConfig) -> usize {
         .expect("could not parse key to usize")
 }
 
-fn verify_filesystem_settings(answer: &Answer, setup_info: &SetupInfo) -> Result<()> {
+fn verify_disks_settings(
+    answer: &Answer,
+    runtime_info: &RuntimeInfo,
+    setup_info: &SetupInfo,
+    config: &InstallConfig,
+) -> Result<()> {
+    info!("Verifying disk settings");
+
+    let disks: Vec<Disk> = match config.filesys {
+        FsType::Ext4 | FsType::Xfs => runtime_info
+            .disks
+            .iter()
+            .filter(|d| Some(&d.path) == config.target_hd.as_ref())
+            .cloned()
+            .collect(),
+        FsType::Zfs(_) | FsType::Btrfs(_) => config
+            .disk_selection
+            .keys()
+            .filter_map(|index| runtime_info.disks.iter().find(|d| &d.index == index))
+            .cloned()
+            .collect(),
+    };
+
+    check_bootdisk_config(config.filesys, &disks).map_err(|err| anyhow!(err))?;
+
+    let product = setup_info.config.product;
+    if let Some(disk) = disks.iter().find(|d| !d.is_valid_bootdisk(product)) {
+        bail!(
+            "disk {} is too small ({:.2} GiB), at least {} GiB are required",
+            disk.path,
+            disk.size,
+            product.min_bootdisk_size()
+        );
+    }
+
+    if let Err(found) = check_disks_existing_data(&disks) {
+        if !answer.disks.wipe_existing_data {
+            bail!(
+                "selected disks already contain data:\n{found}\n\
+                 Set 'disk-setup.wipe_existing_data' to true to overwrite it."
+            );
+        }
+        info!("Wiping existing data on selected disks:\n{found}");
+    }
+
+    Ok(())
+}
+
+pub fn verify_filesystem_settings(answer: &Answer, setup_info: &SetupInfo) -> Result<()> {
     info!("Verifying filesystem settings");
 
     if answer.disks.fs_type.is_btrfs() && !setup_info.config.enable_btrfs {
@@ -308,12 +356,7 @@ pub fn verify_locale_settings(answer: &Answer, locales: &LocaleInfo) -> Result<(
         bail!("keyboard layout '{}' is not valid", &answer.global.keyboard);
     }
 
-    if !locales
-        .cczones
-        .iter()
-        .any(|(_, zones)| zones.contains(&answer.global.timezone))
-        && answer.global.timezone != "UTC"
-    {
+    if !locales.is_valid_timezone(&answer.global.timezone) {
         bail!("timezone '{}' is not valid", &answer.global.timezone);
     }
 
@@ -372,6 +415,7 @@ pub fn parse_answer(
         autoreboot: 1_usize,
         filesys: filesystem,
         hdsize: 0.,
+        esp_size: None,
         swapsize: None,
         maxroot: None,
         minfree: None,
@@ -381,6 +425,7 @@ pub fn parse_answer(
         target_hd: None,
         disk_selection: BTreeMap::new(),
         existing_storage_auto_rename: 1,
+        lvm_encryption: None,
 
         country: answer.global.country.clone(),
         timezone: answer.global.timezone.clone(),
@@ -394,17 +439,36 @@ pub fn parse_answer(
         root_ssh_keys: answer.global.root_ssh_keys.clone(),
 
         mngmt_nic: network_settings.ifname,
+        mngmt_vlan: network_settings.vlan_tag,
 
-        hostname: network_settings.fqdn.host().unwrap().to_string(),
+        hostname: network_settings.fqdn.host().to_owned(),
         domain: network_settings.fqdn.domain(),
         cidr: network_settings.address,
         gateway: network_settings.gateway,
-        dns: network_settings.dns_server,
+        dual_stack_cidr: None,
+        dual_stack_gateway: None,
+        dns: network_settings
+            .dns_servers
+            .first()
+            .copied()
+            .unwrap_or(Ipv4Addr::UNSPECIFIED.into()),
+        secondary_dns: network_settings
+            .dns_servers
+            .iter()
+            .skip(1)
+            .copied()
+            .collect(),
+        search_domain: network_settings.search_domain,
+
+        ntp_servers: Vec::new(),
+        use_dhcp_ntp: true,
+        http_proxy: None,
 
         first_boot: InstallFirstBootSetup::default(),
     };
 
     set_disks(answer, udev_info, runtime_info, &mut config)?;
+    verify_disks_settings(answer, runtime_info, setup_info, &config)?;
     match &answer.disks.fs_options {
         answer::FsOptions::LVM(lvm) => {
             let disk = runtime_info
@@ -415,10 +479,26 @@ pub fn parse_answer(
             config.hdsize = lvm
                 .hdsize
                 .unwrap_or_else(|| disk.map(|d| d.size).unwrap_or_default());
-            config.swapsize = lvm.swapsize;
-            config.maxroot = lvm.maxroot;
-            config.maxvz = lvm.maxvz;
-            config.minfree = lvm.minfree;
+
+            let options = LvmBootdiskOptions {
+                total_size: config.hdsize,
+                swap_size: lvm.swapsize,
+                max_root_size: lvm.maxroot,
+                data_volume: lvm.maxvz.into(),
+                min_lvm_free: lvm.minfree,
+                encryption: None,
+            };
+            if let Some(disk) = disk {
+                options
+                    .validate(disk, setup_info.config.product)
+                    .map_err(|err| anyhow!("invalid LVM options: {err}"))?;
+            }
+
+            // The low-level installer only knows about absolute sizes
+            config.swapsize = options.resolved_swap_size();
+            config.maxroot = options.resolved_max_root_size();
+            config.maxvz = options.resolved_max_data_size();
+            config.minfree = options.resolved_min_lvm_free();
         }
         answer::FsOptions::ZFS(zfs) => {
             let first_selected_disk = get_first_selected_disk(&config);
@@ -426,12 +506,35 @@ pub fn parse_answer(
             config.hdsize = zfs
                 .hdsize
                 .unwrap_or(runtime_info.disks[first_selected_disk].size);
+            let selected_disks = config
+                .disk_selection
+                .keys()
+                .filter_map(|index| runtime_info.disks.get(index.parse::<usize>().ok()?))
+                .cloned()
+                .collect::<Vec<Disk>>();
+
+            // The low-level installer limits the hdsize to the size of each disk
+            let options = ZfsBootdiskOptions {
+                disk_size: smallest_disk(&selected_disks)
+                    .map_or(config.hdsize, |disk| config.hdsize.min(disk.size)),
+                swap_size: zfs.swapsize.unwrap_or_default(),
+                ..ZfsBootdiskOptions::defaults_from(runtime_info, &setup_info.config)
+            };
+            options
+                .validate(&selected_disks)
+                .map_err(|err| anyhow!("invalid ZFS options: {err}"))?;
+
             config.zfs_opts = Some(InstallZfsOption {
-                ashift: zfs.ashift.unwrap_or(12),
+                ashift: zfs
+                    .ashift
+                    .unwrap_or_else(|| recommended_zfs_ashift(&selected_disks)),
                 arc_max: zfs.arc_max.unwrap_or(2048),
                 compress: zfs.compress.unwrap_or(ZfsCompressOption::On),
                 checksum: zfs.checksum.unwrap_or(ZfsChecksumOption::On),
                 copies: zfs.copies.unwrap_or(1),
+                draid_spec: None,
+                swap_size: zfs.swapsize.filter(|size| *size > 0.),
+                encryption: None,
             });
         }
         answer::FsOptions::BTRFS(btrfs) => {
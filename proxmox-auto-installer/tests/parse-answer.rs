@@ -64,6 +64,19 @@ fn run_named_test(name: &str) {
     pretty_assertions::assert_eq!(config, compare);
 }
 
+fn run_named_fail_test(name: &str) -> String {
+    let resource_path = get_test_resource_path().unwrap();
+    let (setup_info, locales, runtime_info, udev_info) = setup_test_basic(&resource_path);
+
+    let answer_path = resource_path.join(format!("parse_answer/{name}.toml"));
+
+    let answer = get_answer(&answer_path).unwrap();
+    match parse_answer(&answer, &udev_info, &runtime_info, &locales, &setup_info) {
+        Ok(_) => panic!("answer '{name}' was expected to fail parsing"),
+        Err(err) => err.to_string(),
+    }
+}
+
 mod tests {
     mod parse_answer {
         use super::super::run_named_test;
@@ -84,8 +97,10 @@ mod tests {
             disk_match,
             disk_match_all,
             disk_match_any,
+            existing_data,
             first_boot,
             hashed_root_password,
+            lvm_percent,
             minimal,
             nic_matching,
             specific_nic,
@@ -93,4 +108,36 @@ mod tests {
             zfs_raid_level_uppercase,
         );
     }
+
+    mod parse_answer_fail {
+        use super::super::run_named_fail_test;
+
+        #[test]
+        fn existing_data_no_wipe() {
+            assert_eq!(
+                run_named_fail_test("existing_data_no_wipe"),
+                "selected disks already contain data:\n\
+                 /dev/nvme4n1 contains a GPT with 3 partitions, vfat filesystem, LVM PV 'pve'\n\
+                 Set 'disk-setup.wipe_existing_data' to true to overwrite it."
+            );
+        }
+
+        #[test]
+        fn lvm_percent_overcommitted() {
+            assert_eq!(
+                run_named_fail_test("lvm_percent_overcommitted"),
+                "invalid LVM options: swap and maximum root volume size together (245.93 GiB) \
+                 exceed the total size (223.57 GiB)"
+            );
+        }
+
+        #[test]
+        fn zfs_swap_too_big() {
+            assert_eq!(
+                run_named_fail_test("zfs_swap_too_big"),
+                "invalid ZFS options: swap size (16.00 GiB) must be at least 0 and smaller than \
+                 the hdsize (16.00 GiB)"
+            );
+        }
+    }
 }
@@ -1,20 +1,30 @@
 use std::collections::HashSet;
 
-use crate::options::{BtrfsRaidLevel, Disk, ZfsRaidLevel};
+use crate::options::{BootMode, BtrfsRaidLevel, Disk, FsType, ZfsBootdiskOptions, ZfsRaidLevel};
 use crate::setup::BootType;
+use crate::utils::format_bytes;
 
-/// Checks a list of disks for duplicate entries, using their index as key.
+/// Checks a list of disks for duplicate entries, using their index as key. Disks whose path or
+/// aliases resolve to the same underlying device, e.g. multiple paths of a multipath device, are
+/// considered duplicates too.
 ///
 /// # Arguments
 ///
 /// * `disks` - A list of disks to check for duplicates.
 pub fn check_for_duplicate_disks(disks: &[Disk]) -> Result<(), &Disk> {
     let mut set = HashSet::new();
+    let mut paths = HashSet::new();
 
     for disk in disks {
         if !set.insert(&disk.index) {
             return Err(disk);
         }
+
+        for path in std::iter::once(&disk.path).chain(&disk.aliases) {
+            if !paths.insert(path) {
+                return Err(disk);
+            }
+        }
     }
 
     Ok(())
@@ -69,18 +79,12 @@ pub fn check_zfs_raid_config(level: ZfsRaidLevel, disks: &[Disk]) -> Result<(),
         }
     };
 
+    check_raid_min_disks(disks, level.get_min_disks())?;
+
     match level {
-        ZfsRaidLevel::Raid0 => check_raid_min_disks(disks, 1)?,
-        ZfsRaidLevel::Raid1 => {
-            check_raid_min_disks(disks, 2)?;
-            for disk in disks {
-                check_mirror_size(&disks[0], disk)?;
-            }
-        }
+        ZfsRaidLevel::Raid0 => {}
         ZfsRaidLevel::Raid10 => {
-            check_raid_min_disks(disks, 4)?;
-
-            if disks.len() % 2 != 0 {
+            if !disks.len().is_multiple_of(2) {
                 return Err(format!(
                     "Needs an even number of disks, currently selected: {}",
                     disks.len(),
@@ -92,21 +96,13 @@ pub fn check_zfs_raid_config(level: ZfsRaidLevel, disks: &[Disk]) -> Result<(),
                 check_mirror_size(&disks[i], &disks[i + 1])?;
             }
         }
-        // For RAID-Z: minimum disks number is level + 2
-        ZfsRaidLevel::RaidZ => {
-            check_raid_min_disks(disks, 3)?;
-            for disk in disks {
-                check_mirror_size(&disks[0], disk)?;
-            }
-        }
-        ZfsRaidLevel::RaidZ2 => {
-            check_raid_min_disks(disks, 4)?;
-            for disk in disks {
-                check_mirror_size(&disks[0], disk)?;
-            }
-        }
-        ZfsRaidLevel::RaidZ3 => {
-            check_raid_min_disks(disks, 5)?;
+        ZfsRaidLevel::Raid1
+        | ZfsRaidLevel::RaidZ
+        | ZfsRaidLevel::RaidZ2
+        | ZfsRaidLevel::RaidZ3
+        | ZfsRaidLevel::DRaid1
+        | ZfsRaidLevel::DRaid2
+        | ZfsRaidLevel::DRaid3 => {
             for disk in disks {
                 check_mirror_size(&disks[0], disk)?;
             }
@@ -126,45 +122,247 @@ pub fn check_zfs_raid_config(level: ZfsRaidLevel, disks: &[Disk]) -> Result<(),
 pub fn check_btrfs_raid_config(level: BtrfsRaidLevel, disks: &[Disk]) -> Result<(), String> {
     // See also Proxmox/Install.pm:get_btrfs_raid_setup()
 
-    match level {
-        BtrfsRaidLevel::Raid0 => check_raid_min_disks(disks, 1)?,
-        BtrfsRaidLevel::Raid1 => check_raid_min_disks(disks, 2)?,
-        BtrfsRaidLevel::Raid10 => check_raid_min_disks(disks, 4)?,
+    check_raid_min_disks(disks, level.get_min_disks())
+}
+
+/// Checks whether the disks of a RAID setup differ significantly in size, i.e. any disk is more
+/// than 10% bigger than the smallest one. In such a case, the space on the bigger disks is wasted,
+/// as all members are limited to the size of the smallest one.
+///
+/// This is meant as a non-fatal warning, the setup is still usable. Only Btrfs setups are
+/// checked, as [`check_zfs_raid_config`] already rejects ZFS mirror and RAIDZ members differing
+/// by more than 10% in size, just like the low-level installer does.
+///
+/// # Arguments
+///
+/// * `fstype` - The targeted filesystem type by the user.
+/// * `disks` - List of disks designated as RAID targets.
+pub fn check_raid_disk_sizes(fstype: FsType, disks: &[Disk]) -> Result<(), String> {
+    let smallest = disks.iter().map(|d| d.size).fold(f64::INFINITY, f64::min);
+    let num = disks.len() as f64;
+
+    let usable = match fstype {
+        FsType::Btrfs(BtrfsRaidLevel::Raid1) => smallest,
+        FsType::Btrfs(BtrfsRaidLevel::Raid10) => smallest * (num / 2.).floor(),
+        // Single disks and stripes always use the full capacity of every disk
+        FsType::Ext4 | FsType::Xfs | FsType::Zfs(_) | FsType::Btrfs(BtrfsRaidLevel::Raid0) => {
+            return Ok(())
+        }
+    };
+
+    if disks.iter().any(|d| d.size - smallest > smallest / 10.) {
+        let to_bytes = |size: f64| (size * 1024. * 1024. * 1024.) as u64;
+        Err(format!(
+            "The selected disks differ by more than 10% in size. All disks are limited to the \
+             size of the smallest one ({}), resulting in about {} of usable space.",
+            format_bytes(to_bytes(smallest)),
+            format_bytes(to_bytes(usable)),
+        ))
+    } else {
+        Ok(())
     }
+}
 
-    Ok(())
+/// Checks whether any of the selected disks already contains data, e.g. an old installation,
+/// which would be lost when installing onto it.
+///
+/// This is meant as a non-fatal warning, the user just needs to acknowledge that the disks will
+/// be wiped.
+///
+/// # Arguments
+///
+/// * `disks` - List of disks designated as installation targets.
+pub fn check_disks_existing_data(disks: &[Disk]) -> Result<(), String> {
+    let found = disks
+        .iter()
+        .filter(|d| d.has_existing_data())
+        .map(|d| format!("{} contains {}", d.path, d.contents))
+        .collect::<Vec<String>>();
+
+    if found.is_empty() {
+        Ok(())
+    } else {
+        Err(found.join("\n"))
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Checks whether the ZFS `ashift` is big enough for the sector sizes of all the selected disks,
+/// as writes smaller than the physical sector size result in a severe performance penalty.
+///
+/// This is meant as a non-fatal warning, the setup is still usable.
+///
+/// # Arguments
+///
+/// * `ashift` - The ZFS `ashift` chosen by the user.
+/// * `disks` - List of disks designated as RAID targets.
+pub fn check_zfs_ashift(ashift: usize, disks: &[Disk]) -> Result<(), String> {
+    let sector_size = |size: Option<usize>| size.map_or("unknown".to_owned(), |s| format!("{s} B"));
+
+    let offending = disks
+        .iter()
+        .filter(|d| d.min_zfs_ashift() > ashift)
+        .map(|d| {
+            format!(
+                "{} (logical/physical sector size {}/{})",
+                d.path,
+                sector_size(d.block_size),
+                sector_size(d.physical_block_size),
+            )
+        })
+        .collect::<Vec<String>>();
+
+    if offending.is_empty() {
+        return Ok(());
+    }
 
-    fn dummy_disk(index: usize) -> Disk {
-        Disk {
-            index: index.to_string(),
-            path: format!("/dev/dummy{index}"),
-            model: Some("Dummy disk".to_owned()),
-            size: 1024. * 1024. * 1024. * 8.,
-            block_size: Some(512),
-        }
+    let required = disks
+        .iter()
+        .map(Disk::min_zfs_ashift)
+        .max()
+        .unwrap_or(ashift);
+    Err(format!(
+        "An ashift of {ashift} is too small for the sector size of the following disk(s), which \
+         will severely degrade performance: {}. An ashift of at least {required} is recommended.",
+        offending.join(", "),
+    ))
+}
+
+/// Checks whether a swap zvol is to be created on a natively encrypted ZFS pool. Swapping onto
+/// zvols can deadlock under memory pressure, which encryption makes considerably more likely, as
+/// it needs additional memory for every write.
+///
+/// This is meant as a non-fatal warning, the setup is still usable.
+///
+/// # Arguments
+///
+/// * `options` - The ZFS options chosen by the user.
+pub fn check_zfs_swap_encryption(options: &ZfsBootdiskOptions) -> Result<(), &'static str> {
+    if options.swap_size > 0. && options.encryption.is_some() {
+        Err(
+            "A swap zvol on an encrypted ZFS pool can lock up the system under memory pressure. \
+             Consider disabling swap or encryption.",
+        )
+    } else {
+        Ok(())
+    }
+}
+
+/// Disks larger than this (in GiB) cannot be fully addressed by some legacy BIOS firmware.
+const LEGACY_BIOS_MAX_DISK_SIZE: f64 = 2048.;
+
+/// Checks whether any disk of a ZFS setup is larger than 2 TiB while booted in legacy BIOS mode.
+/// Some BIOS firmware cannot read beyond the first 2 TiB of a disk, which can render ZFS pools
+/// unbootable once the bootloader needs data from further back.
+///
+/// This is meant as a non-fatal warning, the setup is still usable.
+///
+/// # Arguments
+///
+/// * `boot_mode` - Firmware the system was booted with.
+/// * `fstype` - The targeted filesystem type by the user.
+/// * `disks` - List of disks designated as RAID targets.
+pub fn check_zfs_legacy_boot_disk_size(
+    boot_mode: BootMode,
+    fstype: FsType,
+    disks: &[Disk],
+) -> Result<(), String> {
+    if boot_mode != BootMode::LegacyBios || !matches!(fstype, FsType::Zfs(_)) {
+        return Ok(());
+    }
+
+    let offending = disks
+        .iter()
+        .filter(|d| d.size > LEGACY_BIOS_MAX_DISK_SIZE)
+        .map(|d| d.path.as_str())
+        .collect::<Vec<&str>>();
+
+    if offending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The following disk(s) are larger than 2 TiB, which some firmware cannot boot from \
+             in legacy BIOS mode: {}. Consider booting the installer via UEFI instead.",
+            offending.join(", "),
+        ))
+    }
+}
+
+/// Checks whether the selected bootdisk(s) are usable for the chosen filesystem, e.g. that
+/// there are no duplicates and enough disks for the RAID level.
+///
+/// # Arguments
+///
+/// * `fstype` - The targeted filesystem type by the user.
+/// * `disks` - List of disks designated as bootdisk targets.
+pub fn check_bootdisk_config(fstype: FsType, disks: &[Disk]) -> Result<(), String> {
+    if disks.is_empty() {
+        return Err(match fstype {
+            FsType::Ext4 | FsType::Xfs => "No bootdisk selected".to_owned(),
+            _ => format!(
+                "{fstype}: No disks selected, need at least {}",
+                fstype.get_min_disks()
+            ),
+        });
     }
 
-    fn dummy_disks(num: usize) -> Vec<Disk> {
-        (0..num).map(dummy_disk).collect()
+    check_for_duplicate_disks(disks)
+        .map_err(|disk| format!("Cannot select same disk twice: {disk}"))?;
+
+    match fstype {
+        FsType::Ext4 | FsType::Xfs if disks.len() > 1 => Err(format!(
+            "Only a single bootdisk is supported, got {}",
+            disks.len()
+        )),
+        FsType::Ext4 | FsType::Xfs => Ok(()),
+        FsType::Zfs(level) => check_zfs_raid_config(level, disks),
+        FsType::Btrfs(level) => check_btrfs_raid_config(level, disks),
     }
+    .map_err(|err| format!("{fstype}: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        options::{
+            DiskContents, DiskSignature, ZfsChecksumOption, ZfsCompressOption, ZfsEncryption,
+            ZfsEncryptionAlgorithm,
+        },
+        testing::{dummy_disk, dummy_disks},
+    };
 
     #[test]
     fn duplicate_disks() {
         assert!(check_for_duplicate_disks(&dummy_disks(2)).is_ok());
         assert_eq!(
             check_for_duplicate_disks(&[
-                dummy_disk(0),
-                dummy_disk(1),
-                dummy_disk(2),
-                dummy_disk(2),
-                dummy_disk(3),
+                dummy_disk(0, 8.),
+                dummy_disk(1, 8.),
+                dummy_disk(2, 8.),
+                dummy_disk(2, 8.),
+                dummy_disk(3, 8.),
             ]),
-            Err(&dummy_disk(2)),
+            Err(&dummy_disk(2, 8.)),
+        );
+
+        // Multipath device and one of its paths
+        let mpath = Disk {
+            path: "/dev/mapper/mpatha".to_owned(),
+            aliases: vec!["/dev/sdc".to_owned(), "/dev/sdd".to_owned()],
+            ..dummy_disk(0, 8.)
+        };
+        let path = Disk {
+            path: "/dev/sdd".to_owned(),
+            ..dummy_disk(1, 8.)
+        };
+        assert!(check_for_duplicate_disks(&[mpath.clone(), dummy_disk(1, 8.)]).is_ok());
+        assert_eq!(
+            check_for_duplicate_disks(&[mpath.clone(), path.clone()]),
+            Err(&path)
+        );
+        assert_eq!(
+            check_for_duplicate_disks(&[path, mpath.clone()]),
+            Err(&mpath)
         );
     }
 
@@ -177,6 +375,105 @@ mod tests {
         assert!(check_raid_min_disks(&disks, 1).is_ok());
     }
 
+    #[test]
+    fn raid_level_min_disks() {
+        let disks = dummy_disks(10);
+
+        for level in [
+            ZfsRaidLevel::Raid0,
+            ZfsRaidLevel::Raid1,
+            ZfsRaidLevel::Raid10,
+            ZfsRaidLevel::RaidZ,
+            ZfsRaidLevel::RaidZ2,
+            ZfsRaidLevel::RaidZ3,
+            ZfsRaidLevel::DRaid1,
+            ZfsRaidLevel::DRaid2,
+            ZfsRaidLevel::DRaid3,
+        ] {
+            let min = level.get_min_disks();
+            assert!(check_zfs_raid_config(level, &disks[..min - 1]).is_err());
+            assert!(check_zfs_raid_config(level, &disks[..min]).is_ok());
+        }
+
+        for level in [
+            BtrfsRaidLevel::Raid0,
+            BtrfsRaidLevel::Raid1,
+            BtrfsRaidLevel::Raid10,
+        ] {
+            let min = level.get_min_disks();
+            assert!(check_btrfs_raid_config(level, &disks[..min - 1]).is_err());
+            assert!(check_btrfs_raid_config(level, &disks[..min]).is_ok());
+        }
+    }
+
+    #[test]
+    fn bootdisk_config() {
+        let disks = dummy_disks(10);
+
+        assert!(check_bootdisk_config(FsType::Ext4, &[]).is_err());
+        assert_eq!(
+            check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::Raid1), &[]),
+            Err("ZFS (RAID1): No disks selected, need at least 2".to_owned()),
+        );
+        assert_eq!(
+            check_bootdisk_config(FsType::Btrfs(BtrfsRaidLevel::Raid0), &[]),
+            Err("Btrfs (RAID0): No disks selected, need at least 1".to_owned()),
+        );
+        assert!(check_bootdisk_config(FsType::Ext4, &disks[..1]).is_ok());
+        assert!(check_bootdisk_config(FsType::Xfs, &disks[..2]).is_err());
+
+        assert!(check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::Raid1), &disks[..1]).is_err());
+        assert!(check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::Raid1), &disks[..2]).is_ok());
+        assert!(check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::Raid10), &disks[..5]).is_err());
+        assert!(check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::RaidZ3), &disks[..4]).is_err());
+        assert!(check_bootdisk_config(FsType::Btrfs(BtrfsRaidLevel::Raid10), &disks[..4]).is_ok());
+
+        assert!(check_bootdisk_config(
+            FsType::Zfs(ZfsRaidLevel::Raid1),
+            &[dummy_disk(0, 8.), dummy_disk(0, 8.)]
+        )
+        .unwrap_err()
+        .starts_with("Cannot select same disk twice: /dev/dummy0"));
+        assert_eq!(
+            check_bootdisk_config(FsType::Zfs(ZfsRaidLevel::RaidZ), &disks[..2]),
+            Err("ZFS (RAIDZ-1): Need at least 3 disks".to_owned()),
+        );
+    }
+
+    #[test]
+    fn raid_disk_sizes() {
+        let disks = [dummy_disk(0, 240.), dummy_disk(1, 260.)];
+        assert!(check_raid_disk_sizes(FsType::Btrfs(BtrfsRaidLevel::Raid1), &disks).is_ok());
+
+        let disks = [dummy_disk(0, 240.), dummy_disk(1, 2048.)];
+        assert_eq!(
+            check_raid_disk_sizes(FsType::Btrfs(BtrfsRaidLevel::Raid1), &disks),
+            Err(
+                "The selected disks differ by more than 10% in size. All disks are limited to \
+                 the size of the smallest one (240.00 GiB), resulting in about 240.00 GiB of \
+                 usable space."
+                    .to_owned()
+            )
+        );
+        assert!(check_raid_disk_sizes(FsType::Btrfs(BtrfsRaidLevel::Raid0), &disks).is_ok());
+
+        // Mismatched ZFS mirrors are rejected outright instead
+        assert!(check_raid_disk_sizes(FsType::Zfs(ZfsRaidLevel::Raid1), &disks).is_ok());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::Raid1, &disks).is_err());
+
+        let disks = [
+            dummy_disk(0, 240.),
+            dummy_disk(1, 240.),
+            dummy_disk(2, 2048.),
+            dummy_disk(3, 2048.),
+        ];
+        assert!(
+            check_raid_disk_sizes(FsType::Btrfs(BtrfsRaidLevel::Raid10), &disks)
+                .unwrap_err()
+                .ends_with("resulting in about 480.00 GiB of usable space.")
+        );
+    }
+
     #[test]
     fn bios_boot_compat_4kn() {
         for i in 0..10 {
@@ -218,11 +515,14 @@ mod tests {
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid0, &disks).is_ok());
 
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid1, &[]).is_err());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::Raid1, &disks[..1]).is_err());
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid1, &disks[..2]).is_ok());
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid1, &disks).is_ok());
 
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid10, &[]).is_err());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::Raid10, &disks[..3]).is_err());
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid10, &dummy_disks(4)).is_ok());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::Raid10, &disks[..5]).is_err());
         assert!(check_zfs_raid_config(ZfsRaidLevel::Raid10, &disks).is_ok());
 
         assert!(check_zfs_raid_config(ZfsRaidLevel::RaidZ, &[]).is_err());
@@ -239,5 +539,133 @@ mod tests {
         assert!(check_zfs_raid_config(ZfsRaidLevel::RaidZ3, &disks[..4]).is_err());
         assert!(check_zfs_raid_config(ZfsRaidLevel::RaidZ3, &disks[..5]).is_ok());
         assert!(check_zfs_raid_config(ZfsRaidLevel::RaidZ3, &disks).is_ok());
+
+        assert!(check_zfs_raid_config(ZfsRaidLevel::DRaid1, &disks[..2]).is_err());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::DRaid1, &disks[..3]).is_ok());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::DRaid3, &disks[..4]).is_err());
+        assert!(check_zfs_raid_config(ZfsRaidLevel::DRaid3, &disks).is_ok());
+    }
+
+    #[test]
+    fn zfs_ashift() {
+        let mut disks = dummy_disks(3);
+        assert!(check_zfs_ashift(9, &disks).is_ok());
+
+        disks[1].physical_block_size = Some(4096);
+        disks[2].block_size = Some(4096);
+        disks[2].physical_block_size = Some(4096);
+        assert_eq!(disks[0].min_zfs_ashift(), 9);
+        assert_eq!(disks[1].min_zfs_ashift(), 12);
+        assert_eq!(disks[2].min_zfs_ashift(), 12);
+
+        assert!(check_zfs_ashift(12, &disks).is_ok());
+        assert!(check_zfs_ashift(13, &disks).is_ok());
+        assert_eq!(
+            check_zfs_ashift(9, &disks),
+            Err(
+                "An ashift of 9 is too small for the sector size of the following disk(s), which \
+                 will severely degrade performance: /dev/dummy1 (logical/physical sector size \
+                 512 B/4096 B), /dev/dummy2 (logical/physical sector size 4096 B/4096 B). An \
+                 ashift of at least 12 is recommended."
+                    .to_owned()
+            )
+        );
+
+        disks[0].block_size = None;
+        assert_eq!(disks[0].min_zfs_ashift(), 9);
+    }
+
+    #[test]
+    fn zfs_swap_encryption() {
+        let mut options = ZfsBootdiskOptions {
+            ashift: 12,
+            compress: ZfsCompressOption::default(),
+            checksum: ZfsChecksumOption::default(),
+            copies: 1,
+            arc_max: 0,
+            disk_size: 64.,
+            selected_disks: vec![0, 1],
+            draid_data: None,
+            draid_spares: 0,
+            swap_size: 8.,
+            encryption: None,
+        };
+        assert!(check_zfs_swap_encryption(&options).is_ok());
+
+        options.encryption = Some(ZfsEncryption {
+            algorithm: ZfsEncryptionAlgorithm::Aes256Gcm,
+            passphrase: "12345678".to_owned(),
+        });
+        assert!(check_zfs_swap_encryption(&options).is_err());
+
+        options.swap_size = 0.;
+        assert!(check_zfs_swap_encryption(&options).is_ok());
+    }
+
+    #[test]
+    fn zfs_legacy_boot_disk_size() {
+        let mut disks = dummy_disks(3);
+        disks.iter_mut().for_each(|d| d.size = 2048.);
+        let zfs = FsType::Zfs(ZfsRaidLevel::RaidZ);
+
+        assert!(check_zfs_legacy_boot_disk_size(BootMode::LegacyBios, zfs, &disks).is_ok());
+
+        disks[1].size = 4096.;
+        disks[2].size = 2048.5;
+        assert_eq!(
+            check_zfs_legacy_boot_disk_size(BootMode::LegacyBios, zfs, &disks),
+            Err(
+                "The following disk(s) are larger than 2 TiB, which some firmware cannot boot \
+                 from in legacy BIOS mode: /dev/dummy1, /dev/dummy2. Consider booting the \
+                 installer via UEFI instead."
+                    .to_owned()
+            )
+        );
+
+        let uefi = BootMode::Uefi { secure_boot: false };
+        assert!(check_zfs_legacy_boot_disk_size(uefi, zfs, &disks).is_ok());
+        assert!(check_zfs_legacy_boot_disk_size(
+            BootMode::LegacyBios,
+            FsType::Btrfs(BtrfsRaidLevel::Raid1),
+            &disks
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn existing_data() {
+        let mut disks = dummy_disks(3);
+        assert!(check_disks_existing_data(&disks).is_ok());
+
+        disks[1].contents = DiskContents {
+            partition_table: Some("gpt".to_owned()),
+            partitions: 3,
+            signatures: vec![
+                DiskSignature {
+                    fstype: "vfat".to_owned(),
+                    label: None,
+                },
+                DiskSignature {
+                    fstype: "LVM2_member".to_owned(),
+                    label: Some("pve".to_owned()),
+                },
+            ],
+        };
+        disks[2].contents = DiskContents {
+            signatures: vec![DiskSignature {
+                fstype: "zfs_member".to_owned(),
+                label: Some("rpool".to_owned()),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            check_disks_existing_data(&disks),
+            Err(
+                "/dev/dummy1 contains a GPT with 3 partitions, vfat filesystem, LVM PV 'pve'\n\
+                 /dev/dummy2 contains ZFS pool member 'rpool'"
+                    .to_owned()
+            )
+        );
+        assert!(check_disks_existing_data(&disks[..1]).is_ok());
     }
 }
@@ -1,6 +1,6 @@
 use std::{
     cmp,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
     fs::File,
     io::{self, BufReader},
@@ -13,10 +13,11 @@ use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     options::{
-        BtrfsBootdiskOptions, BtrfsCompressOption, Disk, FsType, ZfsBootdiskOptions,
-        ZfsChecksumOption, ZfsCompressOption,
+        BtrfsBootdiskOptions, BtrfsCompressOption, Disk, DiskContents, DiskEncryption, DiskKind,
+        FsType, ZfsBootdiskOptions, ZfsChecksumOption, ZfsCompressOption, ZfsEncryption,
+        MIN_BOOTDISK_SIZE, MIN_ROOT_SIZE,
     },
-    utils::CidrAddress,
+    utils::{sort_disks, CidrAddress},
 };
 
 #[allow(clippy::upper_case_acronyms)]
@@ -38,6 +39,27 @@ impl ProxmoxProduct {
             Self::PDM => "pdm",
         }
     }
+
+    /// Minimum space needed for the system of this product on a bootdisk in GiB, see
+    /// [`MIN_BOOTDISK_SIZE`].
+    pub fn min_bootdisk_size(self) -> f64 {
+        match self {
+            Self::PVE | Self::PBS | Self::PMG | Self::PDM => MIN_BOOTDISK_SIZE,
+        }
+    }
+
+    /// Minimum size of the root volume of LVM setups for this product in GiB, i.e.
+    /// [`MIN_ROOT_SIZE`], but never less than the space needed for the system itself, see
+    /// [`Self::min_bootdisk_size`].
+    pub fn min_root_size(self) -> f64 {
+        MIN_ROOT_SIZE.max(self.min_bootdisk_size())
+    }
+
+    /// Whether the management interface is bridged into `vmbr0` on the installed system, as done
+    /// by the low-level installer for Proxmox VE.
+    pub fn bridged_network(self) -> bool {
+        self == Self::PVE
+    }
 }
 
 impl fmt::Display for ProxmoxProduct {
@@ -170,17 +192,75 @@ pub struct LocaleInfo {
     #[serde(rename = "country")]
     pub countries: HashMap<String, CountryInfo>,
     pub kmap: HashMap<String, KeyboardMapping>,
+    /// All timezones known to the zoneinfo database.
+    #[serde(default, deserialize_with = "deserialize_zones_set")]
+    pub zones: HashSet<String>,
 }
 
-/// Fetches basic information needed for the installer which is required to work
-pub fn installer_setup(in_test_mode: bool) -> Result<(SetupInfo, LocaleInfo, RuntimeInfo), String> {
-    let base_path = if in_test_mode {
+impl LocaleInfo {
+    /// Returns `true` if the given timezone is a known zoneinfo name, or `UTC`.
+    pub fn is_valid_timezone(&self, tz: &str) -> bool {
+        tz == "UTC" || self.zones.contains(tz)
+    }
+}
+
+/// Returns the directory the low-level installer puts its runtime information into, below
+/// `./testdir` in test mode.
+pub fn runtime_dir(in_test_mode: bool) -> String {
+    if in_test_mode {
         format!("./testdir/{}", crate::RUNTIME_DIR)
     } else {
         crate::RUNTIME_DIR.to_owned()
-    };
+    }
+}
 
-    load_installer_setup_files(base_path)
+/// Fetches basic information needed for the installer which is required to work
+pub fn installer_setup(in_test_mode: bool) -> Result<(SetupInfo, LocaleInfo, RuntimeInfo), String> {
+    load_installer_setup_files(runtime_dir(in_test_mode))
+}
+
+/// Re-runs the hardware detection of the low-level installer and returns the disks found, e.g.
+/// to pick up disks which were hot-plugged after the installer was started.
+///
+/// In test mode, the low-level installer is not run again, only the existing runtime environment
+/// info is re-read.
+pub fn rescan_disks(in_test_mode: bool) -> Result<Vec<Disk>, String> {
+    if !in_test_mode {
+        let output = Command::new("proxmox-low-level-installer")
+            .arg("dump-env")
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|err| format!("Failed to run low-level installer: {err}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Low-level installer failed to detect disks: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+
+    let runtime_info = load_runtime_info(runtime_dir(in_test_mode))?;
+    if runtime_info.disks.is_empty() {
+        Err("The installer could not find any supported hard disks.".to_owned())
+    } else {
+        Ok(runtime_info.disks)
+    }
+}
+
+fn load_runtime_info(runtime_dir: impl AsRef<Path>) -> Result<RuntimeInfo, String> {
+    let mut path = runtime_dir.as_ref().to_path_buf();
+    path.push("run-env-info.json");
+
+    let mut runtime_info: RuntimeInfo = read_json(&path).map_err(|err| {
+        format!(
+            "Failed to retrieve runtime environment info: {}: {err}",
+            path.display()
+        )
+    })?;
+
+    sort_disks(&mut runtime_info.disks);
+    Ok(runtime_info)
 }
 
 pub fn load_installer_setup_files(
@@ -202,19 +282,8 @@ pub fn load_installer_setup_files(
             .map_err(|err| format!("Failed to retrieve locale info: {}: {err}", path.display()))?
     };
 
-    let mut runtime_info: RuntimeInfo = {
-        let mut path = runtime_dir.as_ref().to_path_buf();
-        path.push("run-env-info.json");
-
-        read_json(&path).map_err(|err| {
-            format!(
-                "Failed to retrieve runtime environment info: {}: {err}",
-                path.display()
-            )
-        })?
-    };
+    let runtime_info = load_runtime_info(runtime_dir)?;
 
-    runtime_info.disks.sort();
     if runtime_info.disks.is_empty() {
         Err("The installer could not find any supported hard disks.".to_owned())
     } else if runtime_info.network.interfaces.is_empty() {
@@ -231,6 +300,14 @@ pub struct InstallZfsOption {
     pub checksum: ZfsChecksumOption,
     pub copies: usize,
     pub arc_max: usize,
+    /// vdev specification of dRAID setups, see [`ZfsBootdiskOptions::draid_spec`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draid_spec: Option<String>,
+    /// Size of the swap zvol in GiB, none is created if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swap_size: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<ZfsEncryption>,
 }
 
 impl From<ZfsBootdiskOptions> for InstallZfsOption {
@@ -241,6 +318,9 @@ impl From<ZfsBootdiskOptions> for InstallZfsOption {
             checksum: opts.checksum,
             copies: opts.copies,
             arc_max: opts.arc_max,
+            draid_spec: None,
+            swap_size: (opts.swap_size > 0.).then_some(opts.swap_size),
+            encryption: opts.encryption,
         }
     }
 }
@@ -298,26 +378,112 @@ where
     Ok(result)
 }
 
+fn deserialize_zones_set<'de, D>(deserializer: D) -> Result<HashSet<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: HashMap<String, u32> = Deserialize::deserialize(deserializer)?;
+    Ok(map.into_keys().collect())
+}
+
 fn deserialize_disks_map<'de, D>(deserializer: D) -> Result<Vec<Disk>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let disks =
-        <Vec<(usize, String, f64, String, Option<usize>, String)>>::deserialize(deserializer)?;
+    // Older versions of the low-level installer do not report vendor, serial, kind, physical block
+    // size, stable path, contents and aliases yet, thus only the first six fields are required.
+    struct DiskEntry {
+        index: usize,
+        device: String,
+        size_mb: f64,
+        model: String,
+        logical_bsize: Option<usize>,
+        vendor: String,
+        serial: String,
+        kind: String,
+        physical_bsize: Option<usize>,
+        by_id_path: String,
+        contents: DiskContents,
+        aliases: Vec<String>,
+    }
+
+    struct DiskEntryVisitor;
+
+    impl<'de> de::Visitor<'de> for DiskEntryVisitor {
+        type Value = DiskEntry;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a disk entry with at least six fields")
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<DiskEntry, A::Error> {
+            let index = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let device = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let size_mb = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            let model = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+            let logical_bsize = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(4, &self))?;
+            let _syspath: String = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(5, &self))?;
+
+            Ok(DiskEntry {
+                index,
+                device,
+                size_mb,
+                model,
+                logical_bsize,
+                vendor: seq.next_element()?.unwrap_or_default(),
+                serial: seq.next_element()?.unwrap_or_default(),
+                kind: seq.next_element()?.unwrap_or_default(),
+                physical_bsize: seq.next_element()?.unwrap_or_default(),
+                by_id_path: seq.next_element()?.unwrap_or_default(),
+                contents: seq.next_element()?.unwrap_or_default(),
+                aliases: seq.next_element()?.unwrap_or_default(),
+            })
+        }
+    }
+
+    impl<'de> Deserialize<'de> for DiskEntry {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(DiskEntryVisitor)
+        }
+    }
+
+    let disks = <Vec<DiskEntry>>::deserialize(deserializer)?;
     Ok(disks
         .into_iter()
-        .map(
-            |(index, device, size_mb, model, logical_bsize, _syspath)| Disk {
-                index: index.to_string(),
-                // Linux always reports the size of block devices in sectors, where one sector is
-                // defined as being 2^9 = 512 bytes in size.
-                // https://git.kernel.org/pub/scm/linux/kernel/git/torvalds/linux.git/tree/include/linux/blk_types.h?h=v6.4#n30
-                size: (size_mb * 512.) / 1024. / 1024. / 1024.,
-                block_size: logical_bsize,
-                path: device,
-                model: (!model.is_empty()).then_some(model),
+        .map(|entry| Disk {
+            index: entry.index.to_string(),
+            // Linux always reports the size of block devices in sectors, where one sector is
+            // defined as being 2^9 = 512 bytes in size.
+            // https://git.kernel.org/pub/scm/linux/kernel/git/torvalds/linux.git/tree/include/linux/blk_types.h?h=v6.4#n30
+            size: (entry.size_mb * 512.) / 1024. / 1024. / 1024.,
+            block_size: entry.logical_bsize,
+            physical_block_size: entry.physical_bsize,
+            path: entry.device,
+            model: (!entry.model.is_empty()).then_some(entry.model),
+            vendor: (!entry.vendor.is_empty()).then_some(entry.vendor),
+            serial: (!entry.serial.is_empty()).then_some(entry.serial),
+            by_id_path: (!entry.by_id_path.is_empty()).then_some(entry.by_id_path),
+            contents: entry.contents,
+            kind: match entry.kind.as_str() {
+                "hdd" => DiskKind::Hdd,
+                "ssd" => DiskKind::Ssd,
+                "nvme" => DiskKind::Nvme,
+                _ => DiskKind::Unknown,
             },
-        )
+            aliases: entry.aliases,
+        })
         .collect())
 }
 
@@ -339,11 +505,11 @@ where
         let ip_addr = desc
             .address
             .parse::<IpAddr>()
-            .map_err(|err| de::Error::custom(format!("{:?}", err)))?;
+            .map_err(|err| de::Error::custom(format!("{err}")))?;
 
         result.push(
             CidrAddress::new(ip_addr, desc.prefix)
-                .map_err(|err| de::Error::custom(format!("{:?}", err)))?,
+                .map_err(|err| de::Error::custom(format!("{err}")))?,
         );
     }
 
@@ -440,7 +606,7 @@ pub struct Gateway {
     pub gateway: IpAddr,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum InterfaceState {
     Up,
@@ -527,6 +693,9 @@ pub struct InstallConfig {
 
     pub filesys: FsType,
     pub hdsize: f64,
+    /// Size of the EFI system partition in MiB, sized automatically if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub esp_size: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub swapsize: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -549,6 +718,8 @@ pub struct InstallConfig {
     pub disk_selection: BTreeMap<String, String>,
 
     pub existing_storage_auto_rename: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lvm_encryption: Option<DiskEncryption>,
 
     pub country: String,
     pub timezone: String,
@@ -560,13 +731,38 @@ pub struct InstallConfig {
     pub root_ssh_keys: Vec<String>,
 
     pub mngmt_nic: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mngmt_vlan: Option<u16>,
 
     pub hostname: String,
     pub domain: String,
     #[serde(serialize_with = "serialize_as_display")]
     pub cidr: CidrAddress,
     pub gateway: IpAddr,
+    /// Address and gateway of the other address family, for dual-stack setups.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dual_stack_cidr: Option<CidrAddress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dual_stack_gateway: Option<IpAddr>,
     pub dns: IpAddr,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secondary_dns: Vec<IpAddr>,
+    /// DNS search domain, if set explicitly. Otherwise, `domain` is used.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_domain: Option<String>,
+
+    /// NTP servers to use in addition to the default pool.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ntp_servers: Vec<String>,
+    #[serde(
+        serialize_with = "serialize_bool_as_u32",
+        deserialize_with = "deserialize_bool_from_int"
+    )]
+    pub use_dhcp_ntp: bool,
+
+    /// Full URL of the HTTP proxy to use on the installed system, including any credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<String>,
 
     pub first_boot: InstallFirstBootSetup,
 }
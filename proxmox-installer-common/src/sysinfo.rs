@@ -1,9 +1,28 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fmt, fs, path::Path};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use serde::Serialize;
 
+use crate::options::{BootMode, Disk, DiskContents, DiskKind};
+use crate::setup::{InterfaceState, NetworkInfo};
+use crate::utils::{disk_name_cmp, CidrAddress};
+
 const DMI_PATH: &str = "/sys/devices/virtual/dmi/id";
+const SYS_FIRMWARE_EFI_PATH: &str = "/sys/firmware/efi";
+const EFI_GLOBAL_VARIABLE_GUID: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+const SYS_BLOCK_PATH: &str = "/sys/block";
+const SYS_CLASS_BLOCK_PATH: &str = "/sys/class/block";
+const SYS_CLASS_NET_PATH: &str = "/sys/class/net";
+
+/// `ARPHRD_ETHER` from `<linux/if_arp.h>`, as reported in `/sys/class/net/<iface>/type`.
+const ARPHRD_ETHER: &str = "1";
+
+/// Prefixes of block devices which are never suitable installation targets, mirroring
+/// `Proxmox::Sys::Block::hd_list()`.
+const IGNORED_BLOCK_DEVICES: &[&str] = &["loop", "ram", "zram", "dm-", "md", "fd", "sr"];
+
+/// Minimum size of a block device to be considered as installation target, in bytes.
+const MIN_DISK_SIZE: u64 = 1024 * 1024;
 
 #[derive(Debug, Serialize)]
 pub struct SystemDMI {
@@ -50,3 +69,521 @@ impl SystemDMI {
         Ok(res)
     }
 }
+
+/// Detects whether the system was booted via UEFI, and if so, whether Secure Boot is enabled.
+pub fn detect_boot_mode() -> BootMode {
+    detect_boot_mode_from(Path::new(SYS_FIRMWARE_EFI_PATH))
+}
+
+/// Detects the boot mode based on the given EFI sysfs directory, see [`detect_boot_mode`].
+///
+/// Mirrors `Proxmox::Install::RunEnv::query_installation_environment()`, i.e. the system is
+/// treated as booted via UEFI if the directory exists at all. Secure Boot is considered disabled
+/// if its EFI variable cannot be read.
+pub fn detect_boot_mode_from(efi_path: &Path) -> BootMode {
+    if !efi_path.is_dir() {
+        return BootMode::LegacyBios;
+    }
+
+    // The first four bytes are the variable attributes, followed by the actual value
+    let secure_boot =
+        fs::read(efi_path.join(format!("efivars/SecureBoot-{EFI_GLOBAL_VARIABLE_GUID}")))
+            .map(|content| content.get(4) == Some(&1))
+            .unwrap_or(false);
+
+    BootMode::Uefi { secure_boot }
+}
+
+/// Probes all disks of the system, skipping the medium the installer was booted from.
+///
+/// In contrast to the low-level installer, udev is not queried, thus the disks neither carry
+/// their `/dev/disk/by-id/` path, existing contents nor aliases.
+pub fn probe_disks() -> Result<Vec<Disk>> {
+    let install_medium = install_medium_disk();
+    probe_disks_from(Path::new(SYS_BLOCK_PATH), install_medium.as_deref())
+}
+
+/// Probes all disks found in the given sysfs block directory, see [`probe_disks`]. Loop, RAM,
+/// device-mapper, MD RAID, floppy and optical drives are skipped, as well as devices without a
+/// medium, read-only devices and ones smaller than 1 MiB.
+///
+/// # Arguments
+/// * `sys_block` - Path to the sysfs block directory, usually `/sys/block`
+/// * `install_medium` - Name of the disk the installer was booted from, which is skipped too
+pub fn probe_disks_from(sys_block: &Path, install_medium: Option<&str>) -> Result<Vec<Disk>> {
+    probe_disks_with_skipped_from(sys_block, install_medium).map(|(disks, _)| disks)
+}
+
+/// Reason why a block device was not considered as installation target.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiskSkipReason {
+    /// The installer was booted from this device.
+    InstallMedium,
+    /// Loop, RAM, device-mapper, MD RAID, floppy or optical drive.
+    Ignored,
+    /// No medium present, e.g. a card reader without a card.
+    NoMedium,
+    ReadOnly,
+    /// Smaller than 1 MiB, with the size in bytes.
+    TooSmall(u64),
+}
+
+impl fmt::Display for DiskSkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use DiskSkipReason::*;
+        match self {
+            InstallMedium => write!(f, "installation medium"),
+            Ignored => write!(f, "unsupported device type"),
+            NoMedium => write!(f, "no medium present"),
+            ReadOnly => write!(f, "read-only"),
+            TooSmall(size) => write!(f, "too small ({size} bytes)"),
+        }
+    }
+}
+
+/// A block device which was skipped by [`probe_disks_with_skipped`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedDisk {
+    pub name: String,
+    pub reason: DiskSkipReason,
+}
+
+/// Probes all disks of the system like [`probe_disks`], additionally returning all block
+/// devices which were skipped along with the reason why, to help debugging missing disks.
+pub fn probe_disks_with_skipped() -> Result<(Vec<Disk>, Vec<SkippedDisk>)> {
+    let install_medium = install_medium_disk();
+    probe_disks_with_skipped_from(Path::new(SYS_BLOCK_PATH), install_medium.as_deref())
+}
+
+/// Probes all disks found in the given sysfs block directory, see [`probe_disks_from`] and
+/// [`probe_disks_with_skipped`].
+pub fn probe_disks_with_skipped_from(
+    sys_block: &Path,
+    install_medium: Option<&str>,
+) -> Result<(Vec<Disk>, Vec<SkippedDisk>)> {
+    let mut names = fs::read_dir(sys_block)
+        .with_context(|| format!("failed to read {}", sys_block.display()))?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<String>>>()?;
+    names.sort_by(|a, b| disk_name_cmp(a, b));
+
+    let mut disks = Vec::new();
+    let mut skipped = Vec::new();
+    for name in names {
+        let result = if Some(name.as_str()) == install_medium {
+            Err(DiskSkipReason::InstallMedium)
+        } else if IGNORED_BLOCK_DEVICES.iter().any(|p| name.starts_with(p)) {
+            Err(DiskSkipReason::Ignored)
+        } else {
+            probe_disk(&sys_block.join(&name), &name, disks.len())
+        };
+
+        match result {
+            Ok(disk) => disks.push(disk),
+            Err(reason) => skipped.push(SkippedDisk { name, reason }),
+        }
+    }
+
+    Ok((disks, skipped))
+}
+
+/// Checks whether the block device at the given sysfs path is unsuitable as installation
+/// target, i.e. has no medium, is read-only or smaller than 1 MiB.
+///
+/// # Arguments
+/// * `path` - Path to the sysfs directory of the block device, e.g. `/sys/block/sda`
+pub fn disk_skip_reason(path: &Path) -> Option<DiskSkipReason> {
+    let read = |file: &str| fs::read_to_string(path.join(file)).ok();
+
+    // Linux always reports the size of block devices in 512 byte sectors
+    let size = read("size")
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or(0)
+        * 512;
+
+    if size == 0 {
+        Some(DiskSkipReason::NoMedium)
+    } else if size < MIN_DISK_SIZE {
+        Some(DiskSkipReason::TooSmall(size))
+    } else if read("ro").is_some_and(|ro| ro.trim() == "1") {
+        Some(DiskSkipReason::ReadOnly)
+    } else {
+        None
+    }
+}
+
+/// Reads the metadata of a single disk from sysfs, or the reason why it is not suitable as
+/// installation target.
+fn probe_disk(path: &Path, name: &str, index: usize) -> Result<Disk, DiskSkipReason> {
+    if let Some(reason) = disk_skip_reason(path) {
+        return Err(reason);
+    }
+
+    let read = |file: &str| -> Option<String> {
+        fs::read_to_string(path.join(file))
+            .ok()
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+    };
+
+    let sectors = read("size")
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or(DiskSkipReason::NoMedium)?;
+
+    let kind = if name.starts_with("nvme") {
+        DiskKind::Nvme
+    } else {
+        match read("queue/rotational").as_deref() {
+            Some("1") => DiskKind::Hdd,
+            Some("0") => DiskKind::Ssd,
+            _ => DiskKind::Unknown,
+        }
+    };
+
+    Ok(Disk {
+        index: index.to_string(),
+        path: format!("/dev/{name}"),
+        model: read("device/model").map(|m| m.chars().take(30).collect()),
+        // libata reports "ATA" as vendor for all SATA disks, which does not help anyone
+        vendor: read("device/vendor").filter(|v| v != "ATA"),
+        serial: read("device/serial"),
+        size: (sectors * 512) as f64 / 1024. / 1024. / 1024.,
+        block_size: read("queue/logical_block_size").and_then(|s| s.parse().ok()),
+        physical_block_size: read("queue/physical_block_size").and_then(|s| s.parse().ok()),
+        kind,
+        by_id_path: None,
+        contents: DiskContents::default(),
+        aliases: Vec::new(),
+    })
+}
+
+/// A physical network interface, as detected by [`detect_interfaces`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: String,
+    /// Kernel driver bound to the underlying device, if any.
+    pub driver: Option<String>,
+    pub state: InterfaceState,
+    /// Addresses currently configured on the interface, e.g. obtained via DHCP.
+    pub addresses: Vec<CidrAddress>,
+}
+
+impl NetworkInterface {
+    // avoid display trait as this is not the string representation for a serializer
+    pub fn render(&self) -> String {
+        let link = match self.state {
+            InterfaceState::Up => "link up",
+            InterfaceState::Down => "link down",
+            InterfaceState::Unknown => "link unknown",
+        };
+
+        match &self.driver {
+            Some(driver) => format!("{} \u{2013} {} ({driver}, {link})", self.name, self.mac),
+            None => format!("{} \u{2013} {} ({link})", self.name, self.mac),
+        }
+    }
+}
+
+/// Lists all network interfaces selectable as management interface, sorted by name.
+///
+/// Physical interfaces are detected via sysfs, see [`detect_interfaces`], with their current
+/// addresses taken from the given network information. Interfaces only known from the latter are
+/// included too, without a driver.
+///
+/// # Arguments
+/// * `network` - Network information as gathered by the low-level installer
+pub fn probe_interfaces(network: &NetworkInfo) -> Vec<NetworkInterface> {
+    probe_interfaces_from(Path::new(SYS_CLASS_NET_PATH), network)
+}
+
+/// Lists all network interfaces found in the given sysfs network class directory and network
+/// information, see [`probe_interfaces`].
+///
+/// # Arguments
+/// * `sys_class_net` - Path to the sysfs network class directory, usually `/sys/class/net`
+/// * `network` - Network information as gathered by the low-level installer
+pub fn probe_interfaces_from(sys_class_net: &Path, network: &NetworkInfo) -> Vec<NetworkInterface> {
+    let mut interfaces = detect_interfaces_from(sys_class_net);
+
+    for iface in interfaces.iter_mut() {
+        if let Some(addresses) = network
+            .interfaces
+            .get(&iface.name)
+            .and_then(|info| info.addresses.as_ref())
+        {
+            iface.addresses.clone_from(addresses);
+        }
+    }
+
+    for info in network.interfaces.values() {
+        if !interfaces.iter().any(|iface| iface.name == info.name) {
+            interfaces.push(NetworkInterface {
+                name: info.name.clone(),
+                mac: info.mac.clone(),
+                driver: None,
+                state: info.state.clone(),
+                addresses: info.addresses.clone().unwrap_or_default(),
+            });
+        }
+    }
+
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Detects all physical Ethernet interfaces of the system, sorted by name.
+pub fn detect_interfaces() -> Vec<NetworkInterface> {
+    detect_interfaces_from(Path::new(SYS_CLASS_NET_PATH))
+}
+
+/// Detects all physical Ethernet interfaces found in the given sysfs network class directory,
+/// see [`detect_interfaces`]. The loopback interface and virtual ones like bridges, bonds or VLAN
+/// interfaces are skipped, as these are not backed by a device.
+///
+/// # Arguments
+/// * `sys_class_net` - Path to the sysfs network class directory, usually `/sys/class/net`
+pub fn detect_interfaces_from(sys_class_net: &Path) -> Vec<NetworkInterface> {
+    let Ok(entries) = fs::read_dir(sys_class_net) else {
+        return Vec::new();
+    };
+
+    let mut interfaces = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            let read = |file: &str| -> Option<String> {
+                fs::read_to_string(path.join(file))
+                    .ok()
+                    .map(|s| s.trim().to_owned())
+            };
+
+            if !path.join("device").exists() || read("type")?.as_str() != ARPHRD_ETHER {
+                return None;
+            }
+
+            let driver = read("device/uevent").and_then(|uevent| {
+                uevent
+                    .lines()
+                    .find_map(|line| line.strip_prefix("DRIVER="))
+                    .map(str::to_owned)
+            });
+
+            Some(NetworkInterface {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                mac: read("address")?,
+                driver,
+                state: match read("operstate").as_deref() {
+                    Some("up") => InterfaceState::Up,
+                    Some("down") => InterfaceState::Down,
+                    _ => InterfaceState::Unknown,
+                },
+                addresses: Vec::new(),
+            })
+        })
+        .collect::<Vec<NetworkInterface>>();
+
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    interfaces
+}
+
+/// Returns the name of the disk the installer ISO is mounted from, mirroring
+/// `Proxmox::Sys::Block::get_install_medium_disk()`. If the medium is mounted from a partition,
+/// e.g. for hybrid ISOs, the parent disk is returned.
+fn install_medium_disk() -> Option<String> {
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    let source = mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let source = fields.next()?;
+        let fstype = fields.nth(1)?;
+        (fstype == "iso9660" && source.starts_with("/dev/")).then_some(source)
+    })?;
+
+    let name = fs::canonicalize(source).ok()?.file_name()?.to_owned();
+    let sys_path = fs::canonicalize(Path::new(SYS_CLASS_BLOCK_PATH).join(name)).ok()?;
+
+    let disk = if sys_path.join("partition").exists() {
+        sys_path.parent()?
+    } else {
+        &sys_path
+    };
+
+    Some(disk.file_name()?.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::dummy_disk;
+
+    #[test]
+    fn boot_mode_from_sysfs() {
+        let firmware = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/sysfs/firmware");
+
+        assert_eq!(
+            detect_boot_mode_from(&firmware.join("efi")),
+            BootMode::Uefi { secure_boot: true }
+        );
+        assert_eq!(
+            detect_boot_mode_from(&firmware.join("efi/efivars")),
+            BootMode::Uefi { secure_boot: false }
+        );
+        assert_eq!(
+            detect_boot_mode_from(&firmware.join("nonexistent")),
+            BootMode::LegacyBios
+        );
+    }
+
+    #[test]
+    fn interfaces_from_sysfs() {
+        let sys_class_net =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/sysfs/class/net");
+
+        assert_eq!(
+            detect_interfaces_from(&sys_class_net),
+            [
+                NetworkInterface {
+                    name: "eno1".to_owned(),
+                    mac: "3c:ec:ef:00:00:01".to_owned(),
+                    driver: Some("igb".to_owned()),
+                    state: InterfaceState::Down,
+                    addresses: Vec::new(),
+                },
+                NetworkInterface {
+                    name: "enp2s0".to_owned(),
+                    mac: "3c:ec:ef:00:00:02".to_owned(),
+                    driver: Some("e1000e".to_owned()),
+                    state: InterfaceState::Up,
+                    addresses: Vec::new(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            detect_interfaces_from(&sys_class_net.join("nonexistent")),
+            []
+        );
+    }
+
+    #[test]
+    fn interfaces_probed_with_network_info() {
+        let sys_class_net =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/sysfs/class/net");
+
+        let network: NetworkInfo = serde_json::from_str(
+            r#"{
+                "dns": { "dns": [] },
+                "interfaces": {
+                    "enp2s0": {
+                        "name": "enp2s0",
+                        "index": 2,
+                        "mac": "3c:ec:ef:00:00:02",
+                        "state": "UP",
+                        "addresses": [{ "family": "inet", "address": "192.168.0.2", "prefix": 24 }]
+                    },
+                    "eth9": {
+                        "name": "eth9",
+                        "index": 9,
+                        "mac": "3c:ec:ef:00:00:09",
+                        "state": "DOWN"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let interfaces = probe_interfaces_from(&sys_class_net, &network);
+        assert_eq!(
+            interfaces
+                .iter()
+                .map(NetworkInterface::render)
+                .collect::<Vec<String>>(),
+            [
+                "eno1 \u{2013} 3c:ec:ef:00:00:01 (igb, link down)",
+                "enp2s0 \u{2013} 3c:ec:ef:00:00:02 (e1000e, link up)",
+                "eth9 \u{2013} 3c:ec:ef:00:00:09 (link down)",
+            ]
+        );
+        assert!(interfaces[0].addresses.is_empty());
+        assert_eq!(
+            interfaces[1].addresses,
+            ["192.168.0.2/24".parse::<CidrAddress>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn disks_from_sysfs() {
+        let sys_block = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/sysfs/block");
+
+        let disks = probe_disks_from(&sys_block, Some("sdc")).unwrap();
+        assert_eq!(
+            disks.iter().map(|d| d.path.as_str()).collect::<Vec<&str>>(),
+            ["/dev/nvme0n1", "/dev/sda", "/dev/sdb"]
+        );
+
+        assert_eq!(
+            disks[0],
+            Disk {
+                path: "/dev/nvme0n1".to_owned(),
+                model: Some("Samsung SSD 980 PRO 1TB".to_owned()),
+                serial: Some("S5GXNF0R123456".to_owned()),
+                physical_block_size: Some(512),
+                kind: DiskKind::Nvme,
+                ..dummy_disk(0, (1953514584_u64 * 512) as f64 / 1024. / 1024. / 1024.)
+            }
+        );
+
+        assert_eq!(disks[1].index, "1");
+        assert_eq!(disks[1].model.as_deref(), Some("WDC WD40EFRX-68N32N0"));
+        assert_eq!(disks[1].vendor, None);
+        assert_eq!(disks[1].size, 4.);
+        assert_eq!(disks[1].block_size, Some(512));
+        assert_eq!(disks[1].physical_block_size, Some(4096));
+        assert_eq!(disks[1].kind, DiskKind::Hdd);
+
+        assert_eq!(disks[2].vendor.as_deref(), Some("QEMU"));
+        assert_eq!(disks[2].kind, DiskKind::Ssd);
+
+        // Without an install medium to skip, the USB stick shows up as well
+        let disks = probe_disks_from(&sys_block, None).unwrap();
+        assert_eq!(disks.len(), 4);
+        assert_eq!(disks[3].path, "/dev/sdc");
+
+        assert!(probe_disks_from(&sys_block.join("nonexistent"), None).is_err());
+    }
+
+    #[test]
+    fn skipped_disks_from_sysfs() {
+        let sys_block = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/resources/sysfs/block");
+
+        let (disks, skipped) = probe_disks_with_skipped_from(&sys_block, Some("sdc")).unwrap();
+        assert_eq!(disks.len(), 3);
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|s| (s.name.as_str(), s.reason.to_string()))
+                .collect::<Vec<(&str, String)>>(),
+            [
+                ("dm-0", "unsupported device type".to_owned()),
+                ("loop0", "unsupported device type".to_owned()),
+                ("md0", "unsupported device type".to_owned()),
+                ("sdc", "installation medium".to_owned()),
+                ("sdd", "no medium present".to_owned()),
+                ("sde", "read-only".to_owned()),
+                ("sdf", "too small (524288 bytes)".to_owned()),
+                ("sr0", "unsupported device type".to_owned()),
+                ("zram0", "unsupported device type".to_owned()),
+            ]
+        );
+
+        assert_eq!(disk_skip_reason(&sys_block.join("sda")), None);
+        assert_eq!(
+            disk_skip_reason(&sys_block.join("sde")),
+            Some(DiskSkipReason::ReadOnly)
+        );
+        assert_eq!(
+            disk_skip_reason(&sys_block.join("nonexistent")),
+            Some(DiskSkipReason::NoMedium)
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Fixtures shared by the unit tests of the installer crates. Other crates can use them by
+//! enabling the `testing` feature in their dev-dependencies.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    options::Disk,
+    setup::{BootType, CountryInfo, Dns, KeyboardMapping, LocaleInfo, NetworkInfo, RuntimeInfo},
+};
+
+/// Returns a disk at `/dev/dummy<index>` with the given size in GiB and 512 byte sectors. All
+/// other information, like the medium type or existing data, is unknown resp. empty.
+pub fn dummy_disk(index: usize, size: f64) -> Disk {
+    Disk {
+        index: index.to_string(),
+        path: format!("/dev/dummy{index}"),
+        model: Some("Dummy disk".to_owned()),
+        vendor: None,
+        serial: None,
+        size,
+        block_size: Some(512),
+        physical_block_size: None,
+        kind: Default::default(),
+        by_id_path: None,
+        contents: Default::default(),
+        aliases: Vec::new(),
+    }
+}
+
+/// Returns `num` disks of 8 GiB each, see [`dummy_disk`].
+pub fn dummy_disks(num: usize) -> Vec<Disk> {
+    (0..num).map(|index| dummy_disk(index, 8.)).collect()
+}
+
+/// Returns locale information knowing about Austria and Germany, their timezones and the `de`
+/// and `en-us` keyboard layouts.
+pub fn dummy_locales() -> LocaleInfo {
+    let country = |name: &str, zone: &str| CountryInfo {
+        name: name.to_owned(),
+        zone: zone.to_owned(),
+        kmap: "de".to_owned(),
+    };
+    let kmap = |name: &str, id: &str| KeyboardMapping {
+        name: name.to_owned(),
+        id: id.to_owned(),
+        xkb_layout: id.to_owned(),
+        xkb_variant: String::new(),
+    };
+
+    LocaleInfo {
+        cczones: HashMap::from([
+            ("at".to_owned(), vec!["Europe/Vienna".to_owned()]),
+            ("de".to_owned(), vec!["Europe/Berlin".to_owned()]),
+        ]),
+        countries: HashMap::from([
+            ("at".to_owned(), country("Austria", "Europe/Vienna")),
+            ("de".to_owned(), country("Germany", "Europe/Berlin")),
+        ]),
+        kmap: HashMap::from([
+            ("de".to_owned(), kmap("German", "de")),
+            ("en-us".to_owned(), kmap("U.S. English", "en-us")),
+        ]),
+        zones: HashSet::from(["Europe/Vienna".to_owned(), "Europe/Berlin".to_owned()]),
+    }
+}
+
+/// Returns network information without any configured interface, DNS server or route, i.e. as
+/// if DHCP did not succeed.
+pub fn dummy_network_info() -> NetworkInfo {
+    NetworkInfo {
+        dns: Dns {
+            domain: None,
+            dns: Vec::new(),
+        },
+        routes: None,
+        interfaces: BTreeMap::new(),
+        hostname: None,
+    }
+}
+
+/// Returns runtime information of a UEFI system with 8 GiB of memory and the given disks.
+pub fn dummy_runinfo(disks: Vec<Disk>) -> RuntimeInfo {
+    RuntimeInfo {
+        boot_type: BootType::Efi,
+        country: None,
+        disks,
+        network: dummy_network_info(),
+        total_memory: 8192,
+        hvm_supported: true,
+        secure_boot: None,
+    }
+}
@@ -1,12 +1,51 @@
 use std::{
+    cmp::Ordering,
+    error::Error,
     fmt,
-    net::{AddrParseError, IpAddr},
+    net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr},
     num::ParseIntError,
     str::FromStr,
 };
 
 use serde::Deserialize;
 
+use crate::options::Disk;
+
+/// Family of an IP address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    /// Returns the family of the given address.
+    pub fn of(addr: &IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => IpFamily::V4,
+            IpAddr::V6(_) => IpFamily::V6,
+        }
+    }
+
+    /// Returns the largest valid prefix length for addresses of this family, i.e. its number of
+    /// address bits.
+    pub fn max_mask(&self) -> usize {
+        match self {
+            IpFamily::V4 => 32,
+            IpFamily::V6 => 128,
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpFamily::V4 => write!(f, "IPv4"),
+            IpFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
 /// Possible errors that might occur when parsing CIDR addresses.
 #[derive(Debug)]
 pub enum CidrAddressParseError {
@@ -15,7 +54,37 @@ pub enum CidrAddressParseError {
     /// The IP address part could not be parsed.
     InvalidAddr(AddrParseError),
     /// The mask could not be parsed.
-    InvalidMask(Option<ParseIntError>),
+    InvalidMask(ParseIntError),
+    /// The mask is larger than the number of address bits of the address family.
+    MaskTooLargeForFamily { family: IpFamily, mask: usize },
+}
+
+impl fmt::Display for CidrAddressParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CidrAddressParseError::*;
+        match self {
+            NoDelimiter => write!(f, "missing '/' delimiter"),
+            InvalidAddr(err) => write!(f, "invalid IP address: {err}"),
+            InvalidMask(err) => write!(f, "invalid prefix length: {err}"),
+            MaskTooLargeForFamily { family, mask } => write!(
+                f,
+                "invalid prefix length: /{mask} is too large for an {family} address, \
+                 must be at most /{}",
+                family.max_mask()
+            ),
+        }
+    }
+}
+
+impl Error for CidrAddressParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CidrAddressParseError::NoDelimiter
+            | CidrAddressParseError::MaskTooLargeForFamily { .. } => None,
+            CidrAddressParseError::InvalidAddr(err) => Some(err),
+            CidrAddressParseError::InvalidMask(err) => Some(err),
+        }
+    }
 }
 
 /// An IP address (IPv4 or IPv6), including network mask.
@@ -34,7 +103,7 @@ pub enum CidrAddressParseError {
 /// assert_eq!(ipv4.to_string(), "192.168.0.1/24");
 /// assert_eq!(ipv6.to_string(), "2001:db8::c0a8:1/32");
 /// ```
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct CidrAddress {
     addr: IpAddr,
     mask: usize,
@@ -43,17 +112,48 @@ pub struct CidrAddress {
 impl CidrAddress {
     /// Constructs a new CIDR address.
     ///
-    /// It fails if the mask is invalid for the given IP address.
+    /// It fails if the mask is larger than the number of address bits of the address family,
+    /// i.e. 32 for IPv4 and 128 for IPv6 addresses.
     pub fn new<T: Into<IpAddr>>(addr: T, mask: usize) -> Result<Self, CidrAddressParseError> {
         let addr = addr.into();
+        let family = IpFamily::of(&addr);
 
-        if mask > mask_limit(&addr) {
-            Err(CidrAddressParseError::InvalidMask(None))
+        if mask > family.max_mask() {
+            Err(CidrAddressParseError::MaskTooLargeForFamily { family, mask })
         } else {
             Ok(Self { addr, mask })
         }
     }
 
+    /// Constructs a CIDR address for a single host, i.e. with a `/32` prefix for IPv4 and a `/128`
+    /// prefix for IPv6 addresses.
+    pub fn host<T: Into<IpAddr>>(addr: T) -> Self {
+        let addr = addr.into();
+        let mask = IpFamily::of(&addr).max_mask();
+
+        Self { addr, mask }
+    }
+
+    /// Constructs a new CIDR address from the address and mask given as separate strings, e.g.
+    /// as entered in two distinct input fields.
+    ///
+    /// It fails if either part cannot be parsed or if the mask is invalid for the given IP
+    /// address.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The IP address, e.g. `192.168.0.1`
+    /// * `mask` - The prefix length, e.g. `24`
+    pub fn from_parts(addr: &str, mask: &str) -> Result<Self, CidrAddressParseError> {
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(CidrAddressParseError::InvalidAddr)?;
+
+        let mask = mask.parse().map_err(CidrAddressParseError::InvalidMask)?;
+
+        Self::new(addr, mask)
+    }
+
     /// Returns only the IP address part of the address.
     pub fn addr(&self) -> IpAddr {
         self.addr
@@ -69,10 +169,103 @@ impl CidrAddress {
         self.addr.is_ipv6()
     }
 
+    /// Returns `true` if this address is from a private range, i.e. RFC 1918 for IPv4 or a unique
+    /// local address (RFC 4193) for IPv6.
+    pub fn is_private(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(addr) => addr.is_private(),
+            IpAddr::V6(addr) => addr.is_unique_local(),
+        }
+    }
+
+    /// Returns `true` if this is a loopback address, see [`IpAddr::is_loopback`].
+    pub fn is_loopback(&self) -> bool {
+        self.addr.is_loopback()
+    }
+
+    /// Returns `true` if this is a link-local address, i.e. from `169.254.0.0/16` for IPv4 or
+    /// `fe80::/10` for IPv6.
+    pub fn is_link_local(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(addr) => addr.is_link_local(),
+            IpAddr::V6(addr) => addr.is_unicast_link_local(),
+        }
+    }
+
+    /// Returns `true` if this address is from the IPv4 shared address space `100.64.0.0/10` (RFC
+    /// 6598), as used for carrier-grade NAT. Always `false` for IPv6.
+    pub fn is_shared(&self) -> bool {
+        match self.addr {
+            IpAddr::V4(addr) => addr.octets()[0] == 100 && (addr.octets()[1] & 0xc0) == 64,
+            IpAddr::V6(_) => false,
+        }
+    }
+
+    /// Returns `true` if this is the unspecified address, i.e. `0.0.0.0` or `::`, see
+    /// [`IpAddr::is_unspecified`].
+    pub fn is_unspecified(&self) -> bool {
+        self.addr.is_unspecified()
+    }
+
     /// Returns only the mask part of the address.
     pub fn mask(&self) -> usize {
         self.mask
     }
+
+    /// Returns the mask in its address form, e.g. `255.255.255.0` for a `/24` IPv4 address, as
+    /// needed for configuration files which do not understand the CIDR notation.
+    pub fn netmask(&self) -> IpAddr {
+        match self.addr {
+            IpAddr::V4(_) => Ipv4Addr::from(self.ipv4_netmask()).into(),
+            IpAddr::V6(_) => Ipv6Addr::from(self.ipv6_netmask()).into(),
+        }
+    }
+
+    /// Returns the network address, i.e. the address with all host bits cleared.
+    pub fn network_addr(&self) -> IpAddr {
+        match self.addr {
+            IpAddr::V4(addr) => Ipv4Addr::from(u32::from(addr) & self.ipv4_netmask()).into(),
+            IpAddr::V6(addr) => Ipv6Addr::from(u128::from(addr) & self.ipv6_netmask()).into(),
+        }
+    }
+
+    /// Returns the broadcast address of the network, i.e. the address with all host bits set.
+    ///
+    /// IPv6 has no concept of broadcast addresses, thus `None` is returned for them.
+    pub fn broadcast_addr(&self) -> Option<IpAddr> {
+        match self.addr {
+            IpAddr::V4(addr) => Some(Ipv4Addr::from(u32::from(addr) | !self.ipv4_netmask()).into()),
+            IpAddr::V6(_) => None,
+        }
+    }
+
+    /// Returns `true` if the given address lies within the network of this address, `false`
+    /// otherwise. Addresses of a different family are never contained.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The address to check
+    pub fn contains(&self, other: IpAddr) -> bool {
+        match (self.addr, other) {
+            (IpAddr::V4(addr), IpAddr::V4(other)) => {
+                let mask = self.ipv4_netmask();
+                u32::from(addr) & mask == u32::from(other) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(other)) => {
+                let mask = self.ipv6_netmask();
+                u128::from(addr) & mask == u128::from(other) & mask
+            }
+            _ => false,
+        }
+    }
+
+    fn ipv4_netmask(&self) -> u32 {
+        u32::MAX.checked_shl(32 - self.mask as u32).unwrap_or(0)
+    }
+
+    fn ipv6_netmask(&self) -> u128 {
+        u128::MAX.checked_shl(128 - self.mask as u32).unwrap_or(0)
+    }
 }
 
 impl FromStr for CidrAddress {
@@ -83,17 +276,7 @@ impl FromStr for CidrAddress {
             .split_once('/')
             .ok_or(CidrAddressParseError::NoDelimiter)?;
 
-        let addr = addr.parse().map_err(CidrAddressParseError::InvalidAddr)?;
-
-        let mask = mask
-            .parse()
-            .map_err(|err| CidrAddressParseError::InvalidMask(Some(err)))?;
-
-        if mask > mask_limit(&addr) {
-            Err(CidrAddressParseError::InvalidMask(None))
-        } else {
-            Ok(Self { addr, mask })
-        }
+        Self::from_parts(addr, mask)
     }
 }
 
@@ -110,18 +293,110 @@ impl<'de> Deserialize<'de> for CidrAddress {
     {
         let s: String = Deserialize::deserialize(deserializer)?;
         s.parse()
-            .map_err(|_| serde::de::Error::custom("invalid CIDR"))
+            .map_err(|err| serde::de::Error::custom(format!("invalid CIDR: {err}")))
     }
 }
 
 serde_plain::derive_serialize_from_display!(CidrAddress);
 
-fn mask_limit(addr: &IpAddr) -> usize {
-    if addr.is_ipv4() {
-        32
-    } else {
-        128
+/// Formats a size given in bytes using binary units (KiB, MiB, GiB, ..), picking the largest unit
+/// where the value is at least 1. Anything below 1 KiB is printed as plain bytes, everything else
+/// with two decimal places.
+///
+/// # Examples
+/// ```
+/// use proxmox_installer_common::utils::format_bytes;
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(500107862016), "465.76 GiB");
+/// ```
+pub fn format_bytes(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
     }
+
+    let mut value = size as f64 / 1024.;
+    let mut unit = 0;
+    // Also check the value rounded to two decimal places, such that e.g. 1023.999 KiB gets
+    // printed as 1.00 MiB instead of 1024.00 KiB.
+    while unit < UNITS.len() - 1 && (value * 100.).round() / 100. >= 1024. {
+        value /= 1024.;
+        unit += 1;
+    }
+
+    format!("{value:.2} {}", UNITS[unit])
+}
+
+/// Device name prefixes of disks whose names are suffixed with letters instead of numbers, e.g.
+/// `sda`, `sdb`, .., `sdz`, `sdaa`.
+const LETTER_SUFFIXED_DISK_PREFIXES: &[&str] = &["xvd", "sd", "vd", "hd"];
+
+/// Compares two strings in natural order, i.e. runs of digits are compared by their numeric value,
+/// such that e.g. `nvme2n1` sorts before `nvme10n1`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+
+        let ordering = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let (num_a, rest_a) = split_leading_digits(a);
+            let (num_b, rest_b) = split_leading_digits(b);
+            (a, b) = (rest_a, rest_b);
+
+            // Compare without leading zeros, first by the number of digits, so arbitrarily long
+            // numbers work too
+            let (num_a, num_b) = (num_a.trim_start_matches('0'), num_b.trim_start_matches('0'));
+            num_a.len().cmp(&num_b.len()).then_with(|| num_a.cmp(num_b))
+        } else {
+            (a, b) = (&a[ca.len_utf8()..], &b[cb.len_utf8()..]);
+            ca.cmp(&cb)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_leading_digits(s: &str) -> (&str, &str) {
+    s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()))
+}
+
+/// Compares two disk device paths or names in the order they should be presented to the user.
+///
+/// Names like `sda` are suffixed with letters, which wrap around to two letters after `sdz`, thus
+/// shorter suffixes sort first. Everything else is compared in natural order, see
+/// [`natural_cmp`], e.g. `nvme0n1` < `nvme1n1` < `nvme10n1`.
+pub fn disk_name_cmp(a: &str, b: &str) -> Ordering {
+    let name = |path: &str| path.rsplit('/').next().unwrap_or(path).to_owned();
+    let (a, b) = (name(a), name(b));
+
+    let letter_suffix = |name: &str| -> Option<(&'static str, usize)> {
+        let prefix = LETTER_SUFFIXED_DISK_PREFIXES
+            .iter()
+            .find(|p| name.starts_with(**p))?;
+        let letters = name[prefix.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_lowercase())
+            .count();
+        (letters > 0).then_some((*prefix, letters))
+    };
+
+    match (letter_suffix(&a), letter_suffix(&b)) {
+        (Some((prefix_a, len_a)), Some((prefix_b, len_b))) if prefix_a == prefix_b => {
+            len_a.cmp(&len_b).then_with(|| natural_cmp(&a, &b))
+        }
+        _ => natural_cmp(&a, &b),
+    }
+}
+
+/// Sorts the given disks by their device path, see [`disk_name_cmp`].
+pub fn sort_disks(disks: &mut [Disk]) {
+    disks.sort_by(|a, b| disk_name_cmp(&a.path, &b.path));
 }
 
 /// Possible errors that might occur when parsing FQDNs.
@@ -131,6 +406,12 @@ pub enum FqdnParseError {
     NumericHostname,
     InvalidPart(String),
     TooLong(usize),
+    /// The FQDN is given in absolute form, i.e. with a trailing dot.
+    TrailingDot,
+    /// A label starts or ends with a hyphen.
+    HyphenAtEdge(String),
+    /// The hostname is reserved, see [`Fqdn::RESERVED_HOSTNAMES`].
+    ReservedHostname(String),
 }
 
 impl fmt::Display for FqdnParseError {
@@ -144,6 +425,12 @@ impl fmt::Display for FqdnParseError {
                 "FQDN must only consist of alphanumeric characters and dashes. Invalid part: '{part}'",
             ),
             TooLong(len) => write!(f, "FQDN too long: {len} > {}", Fqdn::MAX_LENGTH),
+            TrailingDot => write!(f, "FQDN must not end with a dot"),
+            HyphenAtEdge(part) => write!(f, "'{part}' must not start or end with a hyphen"),
+            ReservedHostname(host) => write!(
+                f,
+                "hostname '{host}' is reserved for the local host and cannot be used"
+            ),
         }
     }
 }
@@ -164,6 +451,8 @@ impl fmt::Display for FqdnParseError {
 /// Additionally:
 /// - It enforces the restriction as per Bugzilla #1054, in that
 ///   purely numeric hostnames are not allowed - against RFC1123 sec. 2.1.
+/// - It is normalized to lowercase, as names are case-insensitive anyway.
+/// - The absolute form with a trailing dot is rejected, as it is not a valid hostname.
 ///
 /// Some terminology:
 /// - "label" - a single part of a FQDN, e.g. {label}.{label}.{tld}
@@ -184,18 +473,27 @@ impl Fqdn {
     const MAX_LABEL_LENGTH: usize = 63;
     /// Maximum total length of the FQDN
     const MAX_LENGTH: usize = 253;
+    /// Hostnames which always refer to the local host and thus cannot identify it on the network
+    pub const RESERVED_HOSTNAMES: &[&str] = &["localhost"];
 
     pub fn from(fqdn: &str) -> Result<Self, FqdnParseError> {
         if fqdn.len() > Self::MAX_LENGTH {
             return Err(FqdnParseError::TooLong(fqdn.len()));
         }
 
+        if fqdn.len() > 1 && fqdn.ends_with('.') {
+            return Err(FqdnParseError::TrailingDot);
+        }
+
         let parts = fqdn
             .split('.')
-            .map(ToOwned::to_owned)
+            .map(str::to_ascii_lowercase)
             .collect::<Vec<String>>();
 
         for part in &parts {
+            if part.starts_with('-') || part.ends_with('-') {
+                return Err(FqdnParseError::HyphenAtEdge(part.clone()));
+            }
             if !Self::validate_single(part) {
                 return Err(FqdnParseError::InvalidPart(part.clone()));
             }
@@ -207,28 +505,23 @@ impl Fqdn {
             // Do not allow a purely numeric hostname, see:
             // https://bugzilla.proxmox.com/show_bug.cgi?id=1054
             Err(FqdnParseError::NumericHostname)
+        } else if Self::RESERVED_HOSTNAMES.contains(&parts[0].as_str()) {
+            Err(FqdnParseError::ReservedHostname(parts[0].clone()))
         } else {
             Ok(Self { parts })
         }
     }
 
-    pub fn host(&self) -> Option<&str> {
-        self.has_host().then_some(&self.parts[0])
+    /// Returns the short hostname, i.e. the first label, e.g. `pve` for `pve.example.com`.
+    pub fn host(&self) -> &str {
+        // Parsing ensures there are always at least two labels, see `FqdnParseError::MissingHostname`
+        &self.parts[0]
     }
 
+    /// Returns the domain, i.e. everything after the first label, e.g. `example.com` for
+    /// `pve.example.com`.
     pub fn domain(&self) -> String {
-        let parts = if self.has_host() {
-            &self.parts[1..]
-        } else {
-            &self.parts
-        };
-
-        parts.join(".")
-    }
-
-    /// Checks whether the FQDN has a hostname associated with it, i.e. is has more than 1 part.
-    fn has_host(&self) -> bool {
-        self.parts.len() > 1
+        self.parts[1..].join(".")
     }
 
     fn validate_single(s: &str) -> bool {
@@ -252,6 +545,17 @@ impl Fqdn {
     }
 }
 
+/// Checks whether the given string is a valid hostname, e.g. of an NTP server.
+///
+/// In contrast to [`Fqdn`], a single label is enough and labels may be purely numeric, as e.g. in
+/// `0.debian.pool.ntp.org`. Names consisting of numeric labels only are rejected though, as these
+/// are most likely a mistyped IPv4 address.
+pub fn hostname_validate(hostname: &str) -> bool {
+    hostname.len() <= Fqdn::MAX_LENGTH
+        && hostname.split('.').all(Fqdn::validate_single)
+        && !hostname.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
 impl FromStr for Fqdn {
     type Err = FqdnParseError;
 
@@ -277,6 +581,8 @@ impl<'de> Deserialize<'de> for Fqdn {
     }
 }
 
+serde_plain::derive_serialize_from_display!(Fqdn);
+
 impl PartialEq for Fqdn {
     // Case-insensitive comparison, as per RFC 952 "ASSUMPTIONS", RFC 1035 sec. 2.3.3. "Character
     // Case" and RFC 4343 as a whole
@@ -296,6 +602,422 @@ impl PartialEq for Fqdn {
 mod tests {
     use super::*;
 
+    #[test]
+    fn cidr_equality() {
+        use std::collections::HashSet;
+
+        let parse = |s: &str| s.parse::<CidrAddress>().unwrap();
+
+        assert_eq!(parse("192.168.0.1/24"), parse("192.168.0.1/24"));
+        assert_ne!(parse("192.168.0.1/24"), parse("192.168.0.1/16"));
+        assert_ne!(parse("192.168.0.1/24"), parse("192.168.0.2/24"));
+
+        assert_eq!(parse("2001:db8::1/64"), parse("2001:db8::1/64"));
+        assert_eq!(parse("2001:db8::1/64"), parse("2001:0db8::0:1/64"));
+        assert_ne!(parse("2001:db8::1/64"), parse("2001:db8::1/48"));
+
+        let set = HashSet::from([
+            parse("2001:db8::1/64"),
+            parse("2001:0db8::0:1/64"),
+            parse("192.168.0.1/24"),
+        ]);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&parse("2001:db8:0::1/64")));
+    }
+
+    #[test]
+    fn cidr_parse_error_display() {
+        let err = |s: &str| s.parse::<CidrAddress>().unwrap_err();
+
+        assert_eq!(err("10.0.0.1").to_string(), "missing '/' delimiter");
+        assert!(err("10.0.0.1").source().is_none());
+
+        assert_eq!(
+            err("10.0.0.256/24").to_string(),
+            "invalid IP address: invalid IP address syntax"
+        );
+        assert!(err("10.0.0.256/24").source().is_some());
+
+        assert_eq!(
+            err("10.0.0.1/abc").to_string(),
+            "invalid prefix length: invalid digit found in string"
+        );
+        assert!(err("10.0.0.1/abc").source().is_some());
+
+        assert_eq!(
+            err("10.0.0.1/33").to_string(),
+            "invalid prefix length: /33 is too large for an IPv4 address, must be at most /32"
+        );
+        assert!(err("10.0.0.1/33").source().is_none());
+
+        // Usable with `?` in functions returning boxed errors
+        let parse = |s: &str| -> Result<CidrAddress, Box<dyn Error>> { Ok(s.parse()?) };
+        assert_eq!(
+            parse("2001:db8::1").unwrap_err().to_string(),
+            "missing '/' delimiter"
+        );
+    }
+
+    #[test]
+    fn cidr_parse() {
+        let cidr = "2001:db8::1/64".parse::<CidrAddress>().unwrap();
+        assert_eq!(cidr.addr(), Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(cidr.mask(), 64);
+
+        let cidr = "10.0.0.1/32".parse::<CidrAddress>().unwrap();
+        assert_eq!(cidr.addr(), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(cidr.mask(), 32);
+
+        assert!(matches!(
+            "2001:db8::1/129".parse::<CidrAddress>(),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V6,
+                mask: 129
+            })
+        ));
+        assert!(matches!(
+            "10.0.0.1/33".parse::<CidrAddress>(),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V4,
+                mask: 33
+            })
+        ));
+        assert!(matches!(
+            "10.0.0.1".parse::<CidrAddress>(),
+            Err(CidrAddressParseError::NoDelimiter)
+        ));
+    }
+
+    #[test]
+    fn cidr_from_parts() {
+        let cidr = CidrAddress::from_parts("192.168.0.1", "24").unwrap();
+        assert_eq!(cidr.addr(), Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(cidr.mask(), 24);
+        assert_eq!(cidr, "192.168.0.1/24".parse().unwrap());
+
+        let cidr = CidrAddress::from_parts("2001:db8::1", "128").unwrap();
+        assert_eq!(cidr.addr(), Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        assert_eq!(cidr.mask(), 128);
+
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.1", "64"),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V4,
+                mask: 64
+            })
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("2001:db8::1", "129"),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V6,
+                mask: 129
+            })
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.1", "abc"),
+            Err(CidrAddressParseError::InvalidMask(_))
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.1", "-1"),
+            Err(CidrAddressParseError::InvalidMask(_))
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.1", ""),
+            Err(CidrAddressParseError::InvalidMask(_))
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.256", "24"),
+            Err(CidrAddressParseError::InvalidAddr(_))
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("10.0.0.1/24", "24"),
+            Err(CidrAddressParseError::InvalidAddr(_))
+        ));
+        assert!(matches!(
+            CidrAddress::from_parts("", "24"),
+            Err(CidrAddressParseError::InvalidAddr(_))
+        ));
+    }
+
+    #[test]
+    fn cidr_construct() {
+        assert!(CidrAddress::new(Ipv4Addr::new(10, 0, 0, 1), 32).is_ok());
+        assert!(matches!(
+            CidrAddress::new(Ipv4Addr::new(10, 0, 0, 1), 33),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V4,
+                mask: 33
+            })
+        ));
+
+        assert!(CidrAddress::new(Ipv6Addr::LOCALHOST, 128).is_ok());
+        assert!(matches!(
+            CidrAddress::new(Ipv6Addr::LOCALHOST, 129),
+            Err(CidrAddressParseError::MaskTooLargeForFamily {
+                family: IpFamily::V6,
+                mask: 129
+            })
+        ));
+    }
+
+    #[test]
+    fn cidr_mask_family_bounds() {
+        let cases: [(IpAddr, IpFamily); 4] = [
+            (Ipv4Addr::new(10, 0, 0, 1).into(), IpFamily::V4),
+            (Ipv4Addr::UNSPECIFIED.into(), IpFamily::V4),
+            ("2001:db8::1".parse().unwrap(), IpFamily::V6),
+            (Ipv6Addr::UNSPECIFIED.into(), IpFamily::V6),
+        ];
+
+        for (addr, family) in cases {
+            assert_eq!(IpFamily::of(&addr), family);
+            let max = family.max_mask();
+
+            for mask in 0..=max {
+                let cidr = CidrAddress::new(addr, mask).unwrap();
+                assert_eq!(cidr.mask(), mask);
+                assert_eq!(
+                    format!("{addr}/{mask}").parse::<CidrAddress>().unwrap(),
+                    cidr
+                );
+            }
+
+            for mask in [max + 1, max + 2, 255, usize::MAX] {
+                assert!(matches!(
+                    CidrAddress::new(addr, mask),
+                    Err(CidrAddressParseError::MaskTooLargeForFamily { family: f, mask: m })
+                        if f == family && m == mask
+                ));
+                assert!(matches!(
+                    format!("{addr}/{mask}").parse::<CidrAddress>(),
+                    Err(CidrAddressParseError::MaskTooLargeForFamily { family: f, mask: m })
+                        if f == family && m == mask
+                ));
+            }
+        }
+
+        // IPv6 prefixes in the range between both limits are only valid for IPv6
+        assert!(CidrAddress::new(Ipv6Addr::LOCALHOST, 64).is_ok());
+        assert!(CidrAddress::new(Ipv4Addr::LOCALHOST, 64).is_err());
+
+        assert_eq!(
+            CidrAddress::new(Ipv4Addr::LOCALHOST, 33)
+                .unwrap_err()
+                .to_string(),
+            "invalid prefix length: /33 is too large for an IPv4 address, must be at most /32"
+        );
+        assert_eq!(
+            CidrAddress::new(Ipv6Addr::LOCALHOST, 129)
+                .unwrap_err()
+                .to_string(),
+            "invalid prefix length: /129 is too large for an IPv6 address, must be at most /128"
+        );
+    }
+
+    #[test]
+    fn cidr_host() {
+        let cidr = CidrAddress::host(Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(cidr.mask(), 32);
+        assert_eq!(cidr.to_string(), "192.168.0.1/32");
+        assert_eq!(cidr, "192.168.0.1/32".parse().unwrap());
+        assert!(cidr.contains(Ipv4Addr::new(192, 168, 0, 1).into()));
+        assert!(!cidr.contains(Ipv4Addr::new(192, 168, 0, 2).into()));
+
+        let cidr = CidrAddress::host(IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(cidr.mask(), 128);
+        assert_eq!(cidr.to_string(), "::1/128");
+        assert_eq!(cidr.network_addr(), Ipv6Addr::LOCALHOST);
+    }
+
+    #[test]
+    fn cidr_network_boundaries() {
+        let cidr: CidrAddress = "10.0.0.5/24".parse().unwrap();
+        assert_eq!(cidr.network_addr(), Ipv4Addr::new(10, 0, 0, 0));
+        assert_eq!(
+            cidr.broadcast_addr(),
+            Some(Ipv4Addr::new(10, 0, 0, 255).into())
+        );
+        assert!(cidr.contains(Ipv4Addr::new(10, 0, 0, 1).into()));
+        assert!(!cidr.contains(Ipv4Addr::new(10, 0, 1, 1).into()));
+        assert!(!cidr.contains(Ipv6Addr::LOCALHOST.into()));
+
+        let cidr: CidrAddress = "192.168.1.1/0".parse().unwrap();
+        assert_eq!(cidr.network_addr(), Ipv4Addr::UNSPECIFIED);
+        assert_eq!(cidr.broadcast_addr(), Some(Ipv4Addr::BROADCAST.into()));
+        assert!(cidr.contains(Ipv4Addr::new(10, 0, 0, 1).into()));
+        assert!(cidr.contains(Ipv4Addr::BROADCAST.into()));
+        assert!(!cidr.contains(Ipv6Addr::UNSPECIFIED.into()));
+
+        // Point-to-point links, see RFC 3021
+        let cidr: CidrAddress = "198.51.100.7/31".parse().unwrap();
+        assert_eq!(cidr.network_addr(), Ipv4Addr::new(198, 51, 100, 6));
+        assert_eq!(
+            cidr.broadcast_addr(),
+            Some(Ipv4Addr::new(198, 51, 100, 7).into())
+        );
+        assert!(cidr.contains(Ipv4Addr::new(198, 51, 100, 6).into()));
+        assert!(!cidr.contains(Ipv4Addr::new(198, 51, 100, 8).into()));
+
+        let cidr: CidrAddress = "192.168.1.1/32".parse().unwrap();
+        assert_eq!(cidr.network_addr(), Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(
+            cidr.broadcast_addr(),
+            Some(Ipv4Addr::new(192, 168, 1, 1).into())
+        );
+        assert!(cidr.contains(Ipv4Addr::new(192, 168, 1, 1).into()));
+        assert!(!cidr.contains(Ipv4Addr::new(192, 168, 1, 2).into()));
+
+        let cidr: CidrAddress = "2001:db8:0:1::42/64".parse().unwrap();
+        assert_eq!(
+            cidr.network_addr(),
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 0)
+        );
+        assert_eq!(cidr.broadcast_addr(), None);
+        assert!(cidr.contains("2001:db8:0:1:ffff::1".parse().unwrap()));
+        assert!(!cidr.contains("2001:db8:0:2::1".parse().unwrap()));
+        assert!(!cidr.contains(Ipv4Addr::new(10, 0, 0, 1).into()));
+
+        let cidr: CidrAddress = "2001:db8::1/0".parse().unwrap();
+        assert_eq!(cidr.network_addr(), Ipv6Addr::UNSPECIFIED);
+        assert!(cidr.contains("fd00::1".parse().unwrap()));
+
+        let cidr: CidrAddress = "2001:db8::1/128".parse().unwrap();
+        assert_eq!(
+            cidr.network_addr(),
+            "2001:db8::1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert!(cidr.contains("2001:db8::1".parse().unwrap()));
+        assert!(!cidr.contains("2001:db8::2".parse().unwrap()));
+    }
+
+    #[test]
+    fn cidr_classification() {
+        let cidr = |s: &str| s.parse::<CidrAddress>().unwrap();
+
+        let private = cidr("192.168.100.2/24");
+        assert!(private.is_private());
+        assert!(!private.is_loopback());
+        assert!(!private.is_unspecified());
+        assert!(cidr("10.1.2.3/8").is_private());
+        assert!(cidr("172.16.0.1/12").is_private());
+        assert!(cidr("fd12:3456:789a::1/64").is_private());
+
+        let public = cidr("203.0.113.10/24");
+        assert!(!public.is_private());
+        assert!(!public.is_loopback());
+        assert!(!public.is_unspecified());
+        assert!(!cidr("2001:db8::1/64").is_private());
+
+        let unspecified = cidr("0.0.0.0/0");
+        assert!(unspecified.is_unspecified());
+        assert!(!unspecified.is_private());
+        assert!(!unspecified.is_loopback());
+        assert!(cidr("::/0").is_unspecified());
+
+        assert!(cidr("127.0.0.1/8").is_loopback());
+        assert!(CidrAddress::host(Ipv6Addr::LOCALHOST).is_loopback());
+
+        assert!(cidr("169.254.1.2/16").is_link_local());
+        assert!(cidr("fe80::1/64").is_link_local());
+        assert!(!private.is_link_local());
+
+        assert!(cidr("100.64.0.1/10").is_shared());
+        assert!(cidr("100.127.255.254/10").is_shared());
+        assert!(!cidr("100.128.0.1/10").is_shared());
+        assert!(!cidr("100.63.255.254/10").is_shared());
+        assert!(!public.is_shared());
+    }
+
+    #[test]
+    fn cidr_netmask() {
+        let netmask = |s: &str| s.parse::<CidrAddress>().unwrap().netmask();
+
+        assert_eq!(netmask("10.0.0.5/0"), Ipv4Addr::UNSPECIFIED);
+        assert_eq!(netmask("10.0.0.5/24"), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(netmask("10.0.0.5/22"), Ipv4Addr::new(255, 255, 252, 0));
+        assert_eq!(netmask("10.0.0.5/32"), Ipv4Addr::BROADCAST);
+
+        assert_eq!(
+            netmask("2001:db8:0:1::42/64"),
+            Ipv6Addr::new(0xffff, 0xffff, 0xffff, 0xffff, 0, 0, 0, 0)
+        );
+        assert_eq!(
+            netmask("2001:db8:0:1::42/64").to_string(),
+            "ffff:ffff:ffff:ffff::"
+        );
+        assert_eq!(netmask("2001:db8::42/0"), Ipv6Addr::UNSPECIFIED);
+    }
+
+    #[test]
+    fn natural_ordering() {
+        assert_eq!(natural_cmp("nvme2n1", "nvme10n1"), Ordering::Less);
+        assert_eq!(natural_cmp("nvme10n1", "nvme10n1"), Ordering::Equal);
+        assert_eq!(natural_cmp("nvme1n10", "nvme1n2"), Ordering::Greater);
+        assert_eq!(natural_cmp("disk007", "disk7"), Ordering::Equal);
+        assert_eq!(natural_cmp("disk", "disk0"), Ordering::Less);
+        assert_eq!(
+            natural_cmp("a99999999999999999999999", "a1"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn disk_ordering() {
+        let mut names = vec![
+            "/dev/sdab",
+            "/dev/vdb",
+            "/dev/nvme10n1",
+            "/dev/sdb",
+            "/dev/mmcblk1",
+            "/dev/sdz",
+            "/dev/nvme1n1",
+            "/dev/sda",
+            "/dev/vda",
+            "/dev/nvme0n2",
+            "/dev/mmcblk0",
+            "/dev/sdaa",
+            "/dev/nvme0n1",
+            "/dev/xvda",
+        ];
+        names.sort_by(|a, b| disk_name_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "/dev/mmcblk0",
+                "/dev/mmcblk1",
+                "/dev/nvme0n1",
+                "/dev/nvme0n2",
+                "/dev/nvme1n1",
+                "/dev/nvme10n1",
+                "/dev/sda",
+                "/dev/sdb",
+                "/dev/sdz",
+                "/dev/sdaa",
+                "/dev/sdab",
+                "/dev/vda",
+                "/dev/vdb",
+                "/dev/xvda",
+            ]
+        );
+
+        // Works on plain device names as well
+        assert_eq!(disk_name_cmp("sdz", "/dev/sdaa"), Ordering::Less);
+    }
+
+    #[test]
+    fn bytes_formatting() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1), "1 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.00 KiB");
+        assert_eq!(format_bytes(1536), "1.50 KiB");
+        assert_eq!(format_bytes(1024 * 1024 - 1), "1.00 MiB");
+        assert_eq!(format_bytes(1024 * 1024 * 1024), "1.00 GiB");
+        assert_eq!(format_bytes(500107862016), "465.76 GiB");
+        assert_eq!(format_bytes(4 * 1024 * 1024 * 1024 * 1024), "4.00 TiB");
+        assert_eq!(format_bytes(u64::MAX), "16.00 EiB");
+    }
+
     #[test]
     fn fqdn_construct() {
         use FqdnParseError::*;
@@ -305,16 +1027,37 @@ mod tests {
 
         assert_eq!(Fqdn::from("foo"), Err(MissingHostname));
 
-        assert_eq!(Fqdn::from("-foo.com"), Err(InvalidPart("-foo".to_owned())));
-        assert_eq!(Fqdn::from("foo-.com"), Err(InvalidPart("foo-".to_owned())));
-        assert_eq!(Fqdn::from("foo.com-"), Err(InvalidPart("com-".to_owned())));
-        assert_eq!(Fqdn::from("-o-.com"), Err(InvalidPart("-o-".to_owned())));
+        assert_eq!(Fqdn::from("-foo.com"), Err(HyphenAtEdge("-foo".to_owned())));
+        assert_eq!(Fqdn::from("foo-.com"), Err(HyphenAtEdge("foo-".to_owned())));
+        assert_eq!(Fqdn::from("foo.com-"), Err(HyphenAtEdge("com-".to_owned())));
+        assert_eq!(Fqdn::from("-o-.com"), Err(HyphenAtEdge("-o-".to_owned())));
+        assert_eq!(
+            HyphenAtEdge("-foo".to_owned()).to_string(),
+            "'-foo' must not start or end with a hyphen"
+        );
 
         // https://bugzilla.proxmox.com/show_bug.cgi?id=1054
         assert_eq!(Fqdn::from("123.com"), Err(NumericHostname));
+        assert_eq!(Fqdn::from("12345.example.com"), Err(NumericHostname));
         assert!(Fqdn::from("foo123.com").is_ok());
         assert!(Fqdn::from("123foo.com").is_ok());
 
+        assert_eq!(
+            Fqdn::from("localhost.example.com"),
+            Err(ReservedHostname("localhost".to_owned()))
+        );
+        assert_eq!(
+            Fqdn::from("LocalHost.localdomain"),
+            Err(ReservedHostname("localhost".to_owned()))
+        );
+        assert_eq!(
+            ReservedHostname("localhost".to_owned()).to_string(),
+            "hostname 'localhost' is reserved for the local host and cannot be used"
+        );
+        // Only the hostname itself is reserved
+        assert!(Fqdn::from("pve.localhost.example.com").is_ok());
+        assert!(Fqdn::from("localhost1.example.com").is_ok());
+
         assert!(Fqdn::from(&format!("{}.com", "a".repeat(63))).is_ok());
         assert_eq!(
             Fqdn::from(&format!("{}.com", "a".repeat(250))),
@@ -332,15 +1075,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn fqdn_from_str() {
+        use FqdnParseError::*;
+        assert!("pve.example.com".parse::<Fqdn>().is_ok());
+        assert_eq!(
+            "-bad.example.com".parse::<Fqdn>(),
+            Err(HyphenAtEdge("-bad".to_owned()))
+        );
+        assert_eq!("".parse::<Fqdn>(), Err(InvalidPart("".to_owned())));
+        assert_eq!(
+            "pve..example.com".parse::<Fqdn>(),
+            Err(InvalidPart("".to_owned()))
+        );
+        assert_eq!("pve.example.com.".parse::<Fqdn>(), Err(TrailingDot));
+        assert_eq!("a".repeat(300).parse::<Fqdn>(), Err(TooLong(300)));
+    }
+
+    #[test]
+    fn hostname_validation() {
+        assert!(hostname_validate("ntp"));
+        assert!(hostname_validate("ntp.example.com"));
+        assert!(hostname_validate("0.debian.pool.ntp.org"));
+
+        assert!(!hostname_validate(""));
+        assert!(!hostname_validate("ntp..example.com"));
+        assert!(!hostname_validate("-ntp.example.com"));
+        assert!(!hostname_validate("ntp_1.example.com"));
+        assert!(!hostname_validate("192.168.0.300"));
+        assert!(!hostname_validate(&"a".repeat(Fqdn::MAX_LABEL_LENGTH + 1)));
+    }
+
     #[test]
     fn fqdn_parts() {
         let fqdn = Fqdn::from("pve.example.com").unwrap();
-        assert_eq!(fqdn.host().unwrap(), "pve");
+        assert_eq!(fqdn.host(), "pve");
         assert_eq!(fqdn.domain(), "example.com");
         assert_eq!(
             fqdn.parts,
             &["pve".to_owned(), "example".to_owned(), "com".to_owned()]
         );
+
+        // Only the first label is the hostname, the rest is the domain
+        let fqdn = Fqdn::from("pve.sub.example.com").unwrap();
+        assert_eq!(fqdn.host(), "pve");
+        assert_eq!(fqdn.domain(), "sub.example.com");
+
+        let fqdn = Fqdn::from("pve.localdomain").unwrap();
+        assert_eq!(fqdn.host(), "pve");
+        assert_eq!(fqdn.domain(), "localdomain");
+
+        // A bare hostname or the absolute form never make it into an `Fqdn`
+        assert_eq!(Fqdn::from("pve"), Err(FqdnParseError::MissingHostname));
+        assert_eq!(
+            Fqdn::from("pve.example.com."),
+            Err(FqdnParseError::TrailingDot)
+        );
     }
 
     #[test]
@@ -349,6 +1139,27 @@ mod tests {
             Fqdn::from("foo.example.com").unwrap().to_string(),
             "foo.example.com"
         );
+
+        let fqdn = Fqdn::from("PVE.Example.COM").unwrap();
+        assert_eq!(fqdn.to_string(), "pve.example.com");
+        assert_eq!(fqdn.host(), "pve");
+        assert_eq!(fqdn.domain(), "example.com");
+    }
+
+    #[test]
+    fn fqdn_trailing_dot() {
+        use FqdnParseError::*;
+
+        assert_eq!(Fqdn::from("pve.example.com."), Err(TrailingDot));
+        assert_eq!(
+            Fqdn::from("pve.example.com.").unwrap_err().to_string(),
+            "FQDN must not end with a dot"
+        );
+        assert_eq!(Fqdn::from("."), Err(InvalidPart("".to_owned())));
+        assert_eq!(
+            Fqdn::from("pve_1.example.com"),
+            Err(InvalidPart("pve_1".to_owned()))
+        );
     }
 
     #[test]
@@ -294,6 +294,11 @@ impl PostHookInfo {
                 .cloned()
         };
 
+        // The TUI passes on the stable `/dev/disk/by-id/` path of each disk, if available, while
+        // the auto-installer uses the kernel path resp. udev index.
+        let is_disk =
+            |disk: &Disk, name: &str| name == disk.path || Some(name) == disk.by_id_path.as_deref();
+
         let disks = if config.filesys.is_lvm() {
             // If the filesystem is LVM, there is only boot disk. The path (aka. /dev/..)
             // can be found in `config.target_hd`.
@@ -304,7 +309,7 @@ impl PostHookInfo {
                     let is_bootdisk = config
                         .target_hd
                         .as_ref()
-                        .and_then(|hd| (*hd == disk.path).then_some(true));
+                        .and_then(|hd| is_disk(disk, hd).then_some(true));
 
                     anyhow::Ok(DiskInfo {
                         size: (config.hdsize * (SIZE_GIB as f64)) as usize,
@@ -315,15 +320,17 @@ impl PostHookInfo {
                 .collect()
         } else {
             // If the filesystem is not LVM-based (thus Btrfs or ZFS), `config.disk_selection`
-            // contains a list of indices identifiying the boot disks, as given by udev.
-            let selected_disks_indices: Vec<&String> = config.disk_selection.values().collect();
+            // contains a list of indices identifiying the boot disks, as given by udev, or
+            // their paths.
+            let selected_disks: Vec<&String> = config.disk_selection.values().collect();
 
             run_env
                 .disks
                 .iter()
                 .flat_map(|disk| {
-                    let is_bootdisk = selected_disks_indices
-                        .contains(&&disk.index)
+                    let is_bootdisk = selected_disks
+                        .iter()
+                        .any(|name| **name == disk.index || is_disk(disk, name))
                         .then_some(true);
 
                     anyhow::Ok(DiskInfo {
@@ -593,7 +600,7 @@ impl PostHookInfo {
 /// # Arguments
 ///
 /// * `callback` - Callback to call with the absolute path where the chroot environment root is
-///                mounted.
+///   mounted.
 fn with_chroot<R, F: FnOnce(&str) -> Result<R>>(callback: F) -> Result<R> {
     let ec = Command::new("proxmox-chroot")
         .arg("prepare")
@@ -0,0 +1,524 @@
+//! Answer files for populating the [`InstallerOptions`] non-interactively, e.g. for headless
+//! installations. The format is the same as for the automated installer, see
+//! [`proxmox_auto_installer::answer::Answer`]:
+//!
+//! ```toml
+//! [global]
+//! keyboard = "de"
+//! country = "at"
+//! fqdn = "pve.example.com"
+//! mailto = "root@example.com"
+//! timezone = "Europe/Vienna"
+//! root_password = "123456789"
+//!
+//! [network]
+//! source = "from-dhcp"
+//!
+//! [disk-setup]
+//! filesystem = "zfs"
+//! zfs.raid = "raid1"
+//! disk_list = ["sda", "sdb"]
+//! ```
+//!
+//! Settings only supported by the automated installer, like hashed root passwords, SSH keys,
+//! post-installation webhooks or first-boot hooks, are rejected. Everything the answer format has
+//! no notion of, e.g. the NTP servers or a dual-stack setup, is kept as is.
+
+use std::fmt;
+
+use crate::options::{
+    BuildError, InstallerError, InstallerOptions, InstallerOptionsBuilder, PasswordOptions,
+};
+use proxmox_auto_installer::{
+    answer::{Answer, FsOptions},
+    udevinfo::UdevInfo,
+    utils::{apply_network_settings, get_selected_disks, verify_filesystem_settings},
+};
+use proxmox_installer_common::{
+    disk_checks::check_disks_existing_data,
+    options::{
+        recommended_zfs_ashift, AdvancedBootdiskOptions, BootdiskOptions, BtrfsBootdiskOptions,
+        Disk, LvmBootdiskOptions, TimezoneOptions, ZfsBootdiskOptions,
+    },
+    setup::{LocaleInfo, RuntimeInfo, SetupInfo},
+};
+
+/// Possible errors when parsing an answer file, see [`parse_answer`].
+#[derive(Debug, PartialEq)]
+pub enum AnswerError {
+    /// The answer file is not valid TOML or does not match the answer format. Holds the key the
+    /// error was found at if known, e.g. `"network.cidr"`, and the error itself.
+    Parse(Option<String>, String),
+    /// A key is set which only the automated installer supports, e.g. `"global.root_ssh_keys"`.
+    Unsupported(&'static str),
+    /// A required key is missing, e.g. `"global.root_password"`.
+    Missing(&'static str),
+    /// The disks or the network interface could not be selected as given.
+    Selection(String),
+    /// The selected disks already contain data, but `disk-setup.wipe_existing_data` is not set.
+    ExistingData(String),
+    /// All values could be parsed, but the resulting options are not valid.
+    Invalid(Vec<InstallerError>),
+}
+
+impl fmt::Display for AnswerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AnswerError::*;
+        match self {
+            Parse(Some(key), err) => write!(f, "failed to parse answer file at '{key}': {err}"),
+            Parse(None, err) => write!(f, "failed to parse answer file: {err}"),
+            Unsupported(key) => write!(f, "'{key}' is only supported by the automated installer"),
+            Missing(key) => write!(f, "missing required key '{key}'"),
+            Selection(err) => write!(f, "{err}"),
+            ExistingData(found) => write!(
+                f,
+                "selected disks already contain data:\n{found}\n\
+                 Set 'disk-setup.wipe_existing_data' to true to overwrite it."
+            ),
+            Invalid(errors) => write!(
+                f,
+                "{}",
+                errors
+                    .iter()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Parses an answer file and builds a complete, validated set of installer options from it.
+/// Anything the answer format does not cover is taken from `defaults`.
+///
+/// # Arguments
+/// * `input` - Contents of the answer file
+/// * `defaults` - Options to use for everything not set in the answer file
+/// * `setup` - Setup information, for the product to be installed
+/// * `runinfo` - Runtime information, for the available disks
+/// * `udev` - udev information, for matching the disk and network interface filters
+/// * `locales` - Locale information, for checking the timezone
+pub fn parse_answer(
+    input: &str,
+    defaults: &InstallerOptions,
+    setup: &SetupInfo,
+    runinfo: &RuntimeInfo,
+    udev: &UdevInfo,
+    locales: &LocaleInfo,
+) -> Result<InstallerOptions, AnswerError> {
+    let answer: Answer = toml::from_str(input).map_err(|err: toml::de::Error| {
+        let key = err.span().and_then(|span| key_at(input, span.start));
+        AnswerError::Parse(key, err.to_string())
+    })?;
+
+    if answer.global.root_password_hashed.is_some() {
+        return Err(AnswerError::Unsupported("global.root_password_hashed"));
+    } else if !answer.global.root_ssh_keys.is_empty() {
+        return Err(AnswerError::Unsupported("global.root_ssh_keys"));
+    } else if answer.global.reboot_on_error {
+        return Err(AnswerError::Unsupported("global.reboot_on_error"));
+    } else if answer.post_installation_webhook.is_some() {
+        return Err(AnswerError::Unsupported("post-installation-webhook"));
+    } else if answer.first_boot.is_some() {
+        return Err(AnswerError::Unsupported("first-boot"));
+    }
+
+    let password = PasswordOptions {
+        email: answer.global.mailto.clone(),
+        root_password: answer
+            .global
+            .root_password
+            .clone()
+            .ok_or(AnswerError::Missing("global.root_password"))?,
+    };
+
+    let timezone = TimezoneOptions {
+        country: answer.global.country.clone(),
+        timezone: answer.global.timezone.clone(),
+        kb_layout: answer.global.keyboard,
+    };
+
+    let mut network = defaults.network.clone();
+    apply_network_settings(&answer, udev, &mut network)
+        .map_err(|err| AnswerError::Selection(format!("network interface: {err}")))?;
+
+    InstallerOptionsBuilder::new(setup.config.product, locales)
+        .bootdisk(parse_bootdisk(&answer, defaults, setup, runinfo, udev)?)
+        .timezone(timezone)
+        .password(password)
+        .network(network)
+        .ntp(defaults.ntp.clone())
+        .proxy(defaults.proxy.clone())
+        .autoreboot(defaults.autoreboot)
+        .build()
+        .map_err(|err| match err {
+            BuildError::Missing(section) => AnswerError::Missing(section),
+            BuildError::Invalid(errors) => AnswerError::Invalid(errors),
+        })
+}
+
+/// Returns the full name of the key on the line containing the given offset, e.g.
+/// `"network.cidr"`, or the name of the table if the offset points at a table header.
+fn key_at(input: &str, offset: usize) -> Option<String> {
+    let line_start = input.get(..offset)?.rfind('\n').map_or(0, |pos| pos + 1);
+    let table = |line: &str| {
+        let line = line.trim();
+        line.strip_prefix('[')
+            .and_then(|l| l.strip_suffix(']'))
+            .map(|name| name.trim_matches(['[', ']']).trim().to_owned())
+    };
+
+    let line = input[line_start..].lines().next()?;
+    if let Some(name) = table(line) {
+        return Some(name);
+    }
+
+    let key = line.split_once('=')?.0.trim();
+    match input[..line_start].lines().rev().find_map(table) {
+        Some(table) => Some(format!("{table}.{key}")),
+        None => Some(key.to_owned()),
+    }
+}
+
+/// Builds the bootdisk options from the `disk-setup` section, using the same defaults as the
+/// automated installer for all options not given.
+fn parse_bootdisk(
+    answer: &Answer,
+    defaults: &InstallerOptions,
+    setup: &SetupInfo,
+    runinfo: &RuntimeInfo,
+    udev: &UdevInfo,
+) -> Result<BootdiskOptions, AnswerError> {
+    verify_filesystem_settings(answer, setup)
+        .map_err(|err| AnswerError::Selection(err.to_string()))?;
+
+    let disks: Vec<Disk> = get_selected_disks(answer, udev, runinfo)
+        .map_err(|err| AnswerError::Selection(err.to_string()))?
+        .into_iter()
+        .cloned()
+        .collect();
+
+    if !answer.disks.wipe_existing_data {
+        check_disks_existing_data(&disks).map_err(AnswerError::ExistingData)?;
+    }
+
+    let selected_disks = disks
+        .iter()
+        .filter_map(|disk| runinfo.disks.iter().position(|d| d.path == disk.path))
+        .collect();
+    // All RAID members are limited to the size of the smallest one
+    let smallest = disks.iter().map(|d| d.size).fold(f64::INFINITY, f64::min);
+
+    let advanced = match &answer.disks.fs_options {
+        FsOptions::LVM(lvm) => AdvancedBootdiskOptions::Lvm(LvmBootdiskOptions {
+            total_size: lvm.hdsize.unwrap_or(disks[0].size),
+            swap_size: lvm.swapsize,
+            max_root_size: lvm.maxroot,
+            data_volume: lvm.maxvz.into(),
+            min_lvm_free: lvm.minfree,
+            encryption: None,
+        }),
+        FsOptions::ZFS(zfs) => {
+            let defaults = ZfsBootdiskOptions::defaults_from(runinfo, &setup.config);
+            AdvancedBootdiskOptions::Zfs(ZfsBootdiskOptions {
+                ashift: zfs.ashift.unwrap_or_else(|| recommended_zfs_ashift(&disks)),
+                compress: zfs.compress.unwrap_or_default(),
+                checksum: zfs.checksum.unwrap_or_default(),
+                copies: zfs.copies.unwrap_or(1),
+                arc_max: zfs.arc_max.unwrap_or(defaults.arc_max),
+                disk_size: zfs.hdsize.unwrap_or(smallest),
+                selected_disks,
+                swap_size: zfs.swapsize.unwrap_or_default(),
+                ..defaults
+            })
+        }
+        FsOptions::BTRFS(btrfs) => AdvancedBootdiskOptions::Btrfs(BtrfsBootdiskOptions {
+            disk_size: btrfs.hdsize.unwrap_or(smallest),
+            selected_disks,
+            compress: btrfs.compress.unwrap_or_default(),
+        }),
+    };
+
+    Ok(BootdiskOptions {
+        disks,
+        fstype: answer.disks.fs_type,
+        advanced,
+        boot_mode: defaults.bootdisk.boot_mode,
+        esp_size: defaults.bootdisk.esp_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::PasswordError;
+    use proxmox_installer_common::{
+        options::{DiskContents, FsType, NetworkOptions, ZfsRaidLevel},
+        testing::{dummy_disk, dummy_locales, dummy_network_info, dummy_runinfo},
+        utils::Fqdn,
+    };
+    use std::collections::BTreeMap;
+
+    /// Three disks `/dev/dummy0` to `/dev/dummy2` of 64 GiB each.
+    fn disks() -> Vec<Disk> {
+        (0..3).map(|index| dummy_disk(index, 64.)).collect()
+    }
+
+    /// udev information for the disks of the given runtime information and a single NIC `eth0`.
+    fn dummy_udev(runinfo: &RuntimeInfo) -> UdevInfo {
+        let props = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<String, String>>()
+        };
+
+        UdevInfo {
+            disks: runinfo
+                .disks
+                .iter()
+                .map(|d| (d.index.clone(), props(&[("DEVNAME", &d.path)])))
+                .collect(),
+            nics: BTreeMap::from([(
+                "eth0".to_owned(),
+                props(&[
+                    ("INTERFACE", "eth0"),
+                    ("ID_NET_NAME_MAC", "enx0123456789ab"),
+                ]),
+            )]),
+        }
+    }
+
+    /// Defaults as if the network was fully configured via DHCP on `eth1`.
+    fn dummy_defaults(runinfo: &RuntimeInfo) -> InstallerOptions {
+        let network = NetworkOptions {
+            ifname: "eth1".to_owned(),
+            address: "10.0.0.2/24".parse().unwrap(),
+            gateway: "10.0.0.1".parse().unwrap(),
+            dns_servers: vec!["10.0.0.1".parse().unwrap()],
+            ..NetworkOptions::defaults_from(&SetupInfo::mocked(), &dummy_network_info())
+        };
+
+        InstallerOptions {
+            bootdisk: BootdiskOptions::defaults_from(&runinfo.disks[0]),
+            timezone: TimezoneOptions::detected(runinfo, &dummy_locales()),
+            password: Default::default(),
+            network,
+            ntp: Default::default(),
+            proxy: None,
+            autoreboot: true,
+        }
+    }
+
+    fn parse_with(runinfo: &RuntimeInfo, input: &str) -> Result<InstallerOptions, AnswerError> {
+        parse_answer(
+            input,
+            &dummy_defaults(runinfo),
+            &SetupInfo::mocked(),
+            runinfo,
+            &dummy_udev(runinfo),
+            &dummy_locales(),
+        )
+    }
+
+    fn parse(input: &str) -> Result<InstallerOptions, AnswerError> {
+        parse_with(&dummy_runinfo(disks()), input)
+    }
+
+    const GLOBAL: &str = r#"
+        [global]
+        keyboard = "de"
+        country = "at"
+        fqdn = "pve.example.com"
+        mailto = "root@example.com"
+        timezone = "Europe/Vienna"
+        root_password = "12345678"
+    "#;
+
+    const MANUAL_NETWORK: &str = r#"
+        [network]
+        source = "from-answer"
+        cidr = "192.168.0.2/24"
+        gateway = "192.168.0.1"
+        dns = "192.168.0.1"
+        filter.ID_NET_NAME_MAC = "*9ab"
+    "#;
+
+    #[test]
+    fn minimal_answer() {
+        let options = parse(&format!(
+            "{GLOBAL}\n[network]\nsource = \"from-dhcp\"\n\
+             [disk-setup]\nfilesystem = \"ext4\"\ndisk_list = [\"dummy1\"]"
+        ))
+        .unwrap();
+
+        assert_eq!(options.password.email, "root@example.com");
+        assert_eq!(options.password.root_password, "12345678");
+        assert_eq!(options.timezone.kb_layout_str(), "de");
+        assert_eq!(options.network.fqdn, Fqdn::from("pve.example.com").unwrap());
+        // Kept from the defaults, as the interface is not matched when using DHCP
+        assert_eq!(options.network.ifname, "eth1");
+        assert_eq!(options.bootdisk.fstype, FsType::Ext4);
+        assert_eq!(options.bootdisk.disks[0].path, "/dev/dummy1");
+        assert!(options.autoreboot);
+    }
+
+    #[test]
+    fn zfs_answer() {
+        let options = parse(&format!(
+            r#"
+            {GLOBAL}
+            {MANUAL_NETWORK}
+            [disk-setup]
+            filesystem = "zfs"
+            zfs.raid = "raidz-1"
+            zfs.ashift = 13
+            zfs.swapsize = 2
+            filter.DEVNAME = "/dev/dummy*"
+            "#
+        ))
+        .unwrap();
+
+        assert_eq!(options.network.ifname, "eth0");
+        assert_eq!(options.network.address.to_string(), "192.168.0.2/24");
+        assert_eq!(options.network.dns_servers.len(), 1);
+        assert_eq!(options.bootdisk.fstype, FsType::Zfs(ZfsRaidLevel::RaidZ));
+        assert_eq!(options.bootdisk.disks.len(), 3);
+        match &options.bootdisk.advanced {
+            AdvancedBootdiskOptions::Zfs(zfs) => {
+                assert_eq!(zfs.selected_disks, [0, 1, 2]);
+                assert_eq!(zfs.ashift, 13);
+                assert_eq!(zfs.swap_size, 2.);
+                assert_eq!(zfs.disk_size, 64.);
+            }
+            other => panic!("unexpected advanced bootdisk options: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_network_fields() {
+        let answer = format!(
+            "{GLOBAL}\n[network]\nsource = \"from-answer\"\nfilter.ID_NET_NAME_MAC = \"*9ab\"\n\
+             [disk-setup]\nfilesystem = \"ext4\"\ndisk_list = [\"dummy0\"]"
+        );
+
+        let err = parse(&answer).unwrap_err();
+        assert!(matches!(&err, AnswerError::Parse(Some(key), _) if key == "network"));
+        let err = err.to_string();
+        assert!(err.starts_with("failed to parse answer file at 'network': "));
+        assert!(err.contains("Field 'cidr' must be set."));
+
+        // The remaining fields are checked in turn
+        let err = parse(&answer.replace(
+            "source = \"from-answer\"",
+            "source = \"from-answer\"\ncidr = \"192.168.0.2/24\"\ndns = \"192.168.0.1\"",
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("Field 'gateway' must be set."));
+    }
+
+    #[test]
+    fn invalid_network_cidr() {
+        let err = parse(&format!(
+            "{GLOBAL}\n{}\n[disk-setup]\nfilesystem = \"ext4\"\ndisk_list = [\"dummy0\"]",
+            MANUAL_NETWORK.replace("192.168.0.2/24", "192.168.0.300/24")
+        ))
+        .unwrap_err();
+
+        assert!(matches!(&err, AnswerError::Parse(Some(key), _) if key == "network.cidr"));
+        let err = err.to_string();
+        assert!(err.starts_with("failed to parse answer file at 'network.cidr': "));
+        assert!(err.contains("invalid CIDR"));
+    }
+
+    #[test]
+    fn key_at_offset() {
+        let input = "[global]\nkeyboard = \"de\"\n\n[disk-setup]\nzfs.raid = \"raid1\"\n";
+
+        assert_eq!(key_at(input, 0).as_deref(), Some("global"));
+        assert_eq!(key_at(input, 12).as_deref(), Some("global.keyboard"));
+        assert_eq!(key_at(input, 29).as_deref(), Some("disk-setup"));
+        assert_eq!(key_at(input, 50).as_deref(), Some("disk-setup.zfs.raid"));
+        assert_eq!(key_at("keyboard = 1", 11).as_deref(), Some("keyboard"));
+        assert_eq!(key_at(input, 1000), None);
+    }
+
+    #[test]
+    fn invalid_disk_selection() {
+        let disk_setup = |setup: &str| format!("{GLOBAL}\n{MANUAL_NETWORK}\n[disk-setup]\n{setup}");
+
+        assert_eq!(
+            parse(&disk_setup(
+                "filesystem = \"zfs\"\nzfs.raid = \"raid1\"\ndisk_list = [\"dummy0\", \"dummy0\"]"
+            )),
+            Err(AnswerError::Selection(
+                "disk /dev/dummy0 is selected more than once".to_owned()
+            ))
+        );
+        assert!(matches!(
+            parse(&disk_setup(
+                "filesystem = \"xfs\"\ndisk_list = [\"dummy0\", \"dummy1\"]"
+            )),
+            Err(AnswerError::Parse(..))
+        ));
+        assert!(matches!(
+            parse(&disk_setup("filesystem = \"ext4\"\ndisk_list = [\"sdx\"]")),
+            Err(AnswerError::Selection(_))
+        ));
+
+        let disk = Disk {
+            contents: DiskContents {
+                partitions: 3,
+                ..Default::default()
+            },
+            ..dummy_disk(0, 64.)
+        };
+        let runinfo = dummy_runinfo(vec![disk]);
+        let answer = |wipe: bool| {
+            disk_setup(&format!(
+                "filesystem = \"ext4\"\ndisk_list = [\"dummy0\"]\nwipe_existing_data = {wipe}"
+            ))
+        };
+        assert!(matches!(
+            parse_with(&runinfo, &answer(false)),
+            Err(AnswerError::ExistingData(_))
+        ));
+        assert!(parse_with(&runinfo, &answer(true)).is_ok());
+    }
+
+    #[test]
+    fn unsupported_answers() {
+        let answer = |global: &str| {
+            format!(
+                "{GLOBAL}{global}\n{MANUAL_NETWORK}\n\
+                 [disk-setup]\nfilesystem = \"ext4\"\ndisk_list = [\"dummy0\"]"
+            )
+        };
+
+        assert_eq!(
+            parse(&answer("root_ssh_keys = [\"ssh-ed25519 AAAA\"]")),
+            Err(AnswerError::Unsupported("global.root_ssh_keys"))
+        );
+        assert_eq!(
+            parse(&answer("root_ssh_keys = [\"ssh-ed25519 AAAA\"]"))
+                .unwrap_err()
+                .to_string(),
+            "'global.root_ssh_keys' is only supported by the automated installer"
+        );
+        assert_eq!(
+            parse(&format!(
+                "{}\n[first-boot]\nsource = \"from-iso\"",
+                answer("")
+            )),
+            Err(AnswerError::Unsupported("first-boot"))
+        );
+
+        // Parses fine, but does not pass validation
+        assert_eq!(
+            parse(&answer("").replace("12345678", "1234")),
+            Err(AnswerError::Invalid(vec![InstallerError::Password(
+                PasswordError::TooShort(4)
+            )]))
+        );
+    }
+}
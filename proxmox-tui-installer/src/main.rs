@@ -1,6 +1,6 @@
 #![forbid(unsafe_code)]
 
-use std::{collections::HashMap, env, net::IpAddr};
+use std::{borrow::Cow, collections::HashMap, env, fs, net::IpAddr, path::Path};
 
 use cursive::{
     event::Event,
@@ -13,13 +13,25 @@ use cursive::{
     Cursive, CursiveRunnable, ScreenId, View, XY,
 };
 
+mod answer;
+
 mod options;
-use options::{InstallerOptions, PasswordOptions};
+use options::{BuildError, InstallerOptions, InstallerOptionsBuilder, PasswordOptions};
 
 use proxmox_installer_common::{
-    options::{email_validate, BootdiskOptions, NetworkOptions, TimezoneOptions},
-    setup::{installer_setup, LocaleInfo, ProxmoxProduct, RuntimeInfo, SetupInfo},
-    utils::Fqdn,
+    disk_checks::{
+        check_disks_existing_data, check_raid_disk_sizes, check_zfs_ashift,
+        check_zfs_legacy_boot_disk_size, check_zfs_swap_encryption,
+    },
+    options::{
+        preferred_bootdisk, AdvancedBootdiskOptions, BootdiskOptions, DualStackAddress,
+        NetworkOptions, NetworkValidationError, TimezoneOptions,
+    },
+    setup::{
+        installer_setup, read_json, runtime_dir, LocaleInfo, ProxmoxProduct, RuntimeInfo, SetupInfo,
+    },
+    sysinfo::{detect_boot_mode, detect_interfaces, probe_interfaces},
+    utils::{CidrAddress, Fqdn},
 };
 
 mod setup;
@@ -28,8 +40,8 @@ mod system;
 
 mod views;
 use views::{
-    BootdiskOptionsView, CidrAddressEditView, FormView, InstallProgressView, TableView,
-    TableViewItem, TimezoneOptionsView,
+    AdvancedBootdiskOptionsCache, BootdiskOptionsView, CidrAddressEditView, FormView,
+    InstallProgressView, TableView, TableViewItem, TimezoneOptionsView,
 };
 
 // TextView::center() seems to garble the first two lines, so fix it manually here.
@@ -143,42 +155,125 @@ struct InstallerState {
     locales: LocaleInfo,
     steps: HashMap<InstallerStep, ScreenId>,
     in_test_mode: bool,
+    /// Disk indices of the RAID setup for which the user acknowledged the disk size warning
+    acked_disk_size_warning: Option<Vec<String>>,
+    /// Advanced bootdisk options of filesystem types the user switched away from
+    bootdisk_cache: AdvancedBootdiskOptionsCache,
 }
 
 fn main() {
     let mut siv = cursive::crossterm();
 
-    let in_test_mode = match env::args().nth(1).as_deref() {
-        Some("-t") => true,
-
-        // Always force the test directory in debug builds
-        _ => cfg!(debug_assertions),
-    };
+    // Always force the test directory in debug builds
+    let mut in_test_mode = cfg!(debug_assertions);
+    // Pre-baked options to start with, see `--dump-options`
+    let mut options_file = None;
+    // Answer file to populate the options from, applied on top of the above
+    let mut answer_file = None;
+    let mut dump_options = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-t" => in_test_mode = true,
+            "--options" => options_file = args.next(),
+            "--answer" => answer_file = args.next(),
+            "--dump-options" => dump_options = true,
+            _ => {}
+        }
+    }
 
     let (setup_info, locales, runtime_info) = match installer_setup(in_test_mode) {
         Ok(result) => result,
         Err(err) => initial_setup_error(&mut siv, &err),
     };
 
+    let mut options = match options_file {
+        Some(path) => match fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|s| InstallerOptions::from_toml_str(&s).map_err(|err| err.to_string()))
+            .and_then(|options| {
+                options
+                    .timezone
+                    .validate(&locales)
+                    .map(|()| options)
+                    .map_err(|err| err.to_string())
+            }) {
+            Ok(options) => options,
+            Err(err) => initial_setup_error(
+                &mut siv,
+                &format!("Failed to load installer options from {path}: {err}"),
+            ),
+        },
+        None => InstallerOptions {
+            bootdisk: BootdiskOptions::defaults_from_with_memory(
+                // Safety: At least one disk must always exist, see installer_setup()
+                preferred_bootdisk(&runtime_info.disks).unwrap(),
+                runtime_info.total_memory,
+            ),
+            timezone: TimezoneOptions::detected(&runtime_info, &locales),
+            password: Default::default(),
+            network: NetworkOptions::defaults_from(&setup_info, &runtime_info.network)
+                .with_first_interface(&detect_interfaces()),
+            ntp: Default::default(),
+            proxy: None,
+            autoreboot: true,
+        },
+    };
+
+    // Answer files contain everything needed, thus the installation is started right away
+    let headless = answer_file.is_some();
+    if let Some(path) = answer_file {
+        options = match fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|s| {
+                let udev_path = Path::new(&runtime_dir(in_test_mode)).join("run-env-udev.json");
+                let udev = read_json(&udev_path)
+                    .map_err(|err| format!("failed to retrieve udev info details: {err}"))?;
+                answer::parse_answer(&s, &options, &setup_info, &runtime_info, &udev, &locales)
+                    .map_err(|err| err.to_string())
+            }) {
+            Ok(options) => options,
+            Err(err) => initial_setup_error(
+                &mut siv,
+                &format!("Failed to load answer file {path}: {err}"),
+            ),
+        };
+    }
+
+    // The boot mode is a property of the running system, thus never taken from the options file
+    options.bootdisk.boot_mode = detect_boot_mode();
+
+    if dump_options {
+        match options.to_toml_string() {
+            Ok(s) => print!("{s}"),
+            Err(err) => {
+                eprintln!("Failed to serialize installer options: {err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     siv.clear_global_callbacks(Event::CtrlChar('c'));
     siv.set_on_pre_event(Event::CtrlChar('c'), trigger_abort_install_dialog);
 
     siv.set_user_data(InstallerState {
-        options: InstallerOptions {
-            bootdisk: BootdiskOptions::defaults_from(&runtime_info.disks[0]),
-            timezone: TimezoneOptions::defaults_from(&runtime_info, &locales),
-            password: Default::default(),
-            network: NetworkOptions::defaults_from(&setup_info, &runtime_info.network),
-            autoreboot: true,
-        },
+        options,
         setup_info,
         runtime_info,
         locales,
         steps: HashMap::new(),
         in_test_mode,
+        acked_disk_size_warning: None,
+        bootdisk_cache: Default::default(),
     });
 
-    switch_to_next_screen(&mut siv, InstallerStep::Licence, &license_dialog);
+    if headless {
+        switch_to_next_screen(&mut siv, InstallerStep::Install, &install_progress_dialog);
+    } else {
+        switch_to_next_screen(&mut siv, InstallerStep::Licence, &license_dialog);
+    }
     siv.run();
 }
 
@@ -187,7 +282,7 @@ fn installer_setup_late(siv: &mut Cursive) {
     let state = siv.user_data::<InstallerState>().cloned().unwrap();
 
     if !state.in_test_mode {
-        let kmap_id = &state.options.timezone.kb_layout;
+        let kmap_id = state.options.timezone.kb_layout_str();
         if let Some(kmap) = state.locales.kmap.get(kmap_id) {
             if let Err(err) = system::set_keyboard_layout(kmap) {
                 display_setup_warning(siv, &format!("Failed to apply keyboard layout: {err}"));
@@ -376,11 +471,30 @@ fn bootdisk_dialog(siv: &mut Cursive) -> InstallerView {
 
             match options {
                 Some(Ok(options)) => {
-                    siv.with_user_data(|state: &mut InstallerState| {
-                        state.options.bootdisk = options;
-                    });
-
-                    switch_to_next_screen(siv, InstallerStep::Timezone, &timezone_dialog);
+                    let disk_set: Vec<String> =
+                        options.disks.iter().map(|d| d.index.clone()).collect();
+                    let acked = siv
+                        .user_data::<InstallerState>()
+                        .map(|state| state.acked_disk_size_warning.as_ref() == Some(&disk_set))
+                        .unwrap_or_default();
+
+                    match check_raid_disk_sizes(options.fstype, &options.disks) {
+                        Err(warning) if !acked => prompt_dialog(
+                            siv,
+                            "Disk size mismatch",
+                            &warning,
+                            "Continue",
+                            Box::new(move |siv| {
+                                siv.with_user_data(|state: &mut InstallerState| {
+                                    state.acked_disk_size_warning = Some(disk_set.clone());
+                                });
+                                bootdisk_dialog_check_ashift(siv, options.clone());
+                            }),
+                            "Cancel",
+                            Box::new(|_| {}),
+                        ),
+                        _ => bootdisk_dialog_check_ashift(siv, options),
+                    }
                 }
 
                 Some(Err(err)) => siv.add_layer(Dialog::info(format!("Invalid values: {err}"))),
@@ -391,6 +505,95 @@ fn bootdisk_dialog(siv: &mut Cursive) -> InstallerView {
     )
 }
 
+/// Warns about a ZFS `ashift` too small for the sector size of any selected disk before
+/// continuing, see [`check_zfs_ashift`].
+fn bootdisk_dialog_check_ashift(siv: &mut Cursive, options: BootdiskOptions) {
+    let warning = match &options.advanced {
+        AdvancedBootdiskOptions::Zfs(zfs) => check_zfs_ashift(zfs.ashift, &options.disks).err(),
+        _ => None,
+    };
+
+    match warning {
+        Some(warning) => prompt_dialog(
+            siv,
+            "Sector size mismatch",
+            &warning,
+            "Continue",
+            Box::new(move |siv| bootdisk_dialog_check_legacy_boot(siv, options.clone())),
+            "Cancel",
+            Box::new(|_| {}),
+        ),
+        None => bootdisk_dialog_check_legacy_boot(siv, options),
+    }
+}
+
+/// Warns about ZFS setups with disks too large for some legacy BIOS firmware before continuing,
+/// see [`check_zfs_legacy_boot_disk_size`].
+fn bootdisk_dialog_check_legacy_boot(siv: &mut Cursive, options: BootdiskOptions) {
+    match check_zfs_legacy_boot_disk_size(options.boot_mode, options.fstype, &options.disks) {
+        Err(warning) => prompt_dialog(
+            siv,
+            "Legacy BIOS boot",
+            &warning,
+            "Continue",
+            Box::new(move |siv| bootdisk_dialog_check_swap_encryption(siv, options.clone())),
+            "Cancel",
+            Box::new(|_| {}),
+        ),
+        Ok(()) => bootdisk_dialog_check_swap_encryption(siv, options),
+    }
+}
+
+/// Warns about a swap zvol on an encrypted ZFS pool before continuing, see
+/// [`check_zfs_swap_encryption`].
+fn bootdisk_dialog_check_swap_encryption(siv: &mut Cursive, options: BootdiskOptions) {
+    let warning = match &options.advanced {
+        AdvancedBootdiskOptions::Zfs(zfs) => check_zfs_swap_encryption(zfs).err(),
+        _ => None,
+    };
+
+    match warning {
+        Some(warning) => prompt_dialog(
+            siv,
+            "Swap on encrypted ZFS",
+            warning,
+            "Continue",
+            Box::new(move |siv| bootdisk_dialog_check_existing_data(siv, options.clone())),
+            "Cancel",
+            Box::new(|_| {}),
+        ),
+        None => bootdisk_dialog_check_existing_data(siv, options),
+    }
+}
+
+/// Asks for an explicit acknowledgement before continuing if any of the selected disks already
+/// contains data, see [`check_disks_existing_data`].
+fn bootdisk_dialog_check_existing_data(siv: &mut Cursive, options: BootdiskOptions) {
+    match check_disks_existing_data(&options.disks) {
+        Err(found) => prompt_dialog(
+            siv,
+            "Existing data found",
+            &format!(
+                "The following disks already contain data, which will be irrevocably lost:\n\n\
+                 {found}\n\nWipe all selected disks and continue?"
+            ),
+            "Wipe and continue",
+            Box::new(move |siv| bootdisk_dialog_next(siv, options.clone())),
+            "Cancel",
+            Box::new(|_| {}),
+        ),
+        Ok(()) => bootdisk_dialog_next(siv, options),
+    }
+}
+
+fn bootdisk_dialog_next(siv: &mut Cursive, options: BootdiskOptions) {
+    siv.with_user_data(|state: &mut InstallerState| {
+        state.options.bootdisk = options;
+    });
+
+    switch_to_next_screen(siv, InstallerStep::Timezone, &timezone_dialog);
+}
+
 fn timezone_dialog(siv: &mut Cursive) -> InstallerView {
     let state = siv.user_data::<InstallerState>().unwrap();
     let options = &state.options.timezone;
@@ -447,17 +650,19 @@ fn password_dialog(siv: &mut Cursive) -> InstallerView {
                     .get_value::<EditView, _>(2)
                     .ok_or("failed to retrieve email")?;
 
-                if root_password.len() < 5 {
-                    Err("password too short, must be at least 5 characters long".to_owned())
-                } else if root_password != confirm_password {
-                    Err("passwords do not match".to_owned())
-                } else if let Err(err) = email_validate(&email) {
+                let options = PasswordOptions {
+                    root_password,
+                    email,
+                };
+
+                if let Err(err) = options.validate_password() {
                     Err(err.to_string())
+                } else if !options.password_matches(&confirm_password) {
+                    Err("passwords do not match".to_owned())
+                } else if let Err(err) = options.validate_email() {
+                    Err(err)
                 } else {
-                    Ok(PasswordOptions {
-                        root_password,
-                        email,
-                    })
+                    Ok(options)
                 }
             });
 
@@ -480,23 +685,32 @@ fn password_dialog(siv: &mut Cursive) -> InstallerView {
 fn network_dialog(siv: &mut Cursive) -> InstallerView {
     let state = siv.user_data::<InstallerState>().unwrap();
     let options = &state.options.network;
-    let ifaces = state.runtime_info.network.interfaces.values();
-    let ifnames = ifaces
-        .clone()
-        .map(|iface| (iface.render(), iface.name.clone()));
-    let mut ifaces_selection = SelectView::new().popup().with_all(ifnames.clone());
-
-    // sort first to always have stable view
-    ifaces_selection.sort();
+    let ifaces = probe_interfaces(&state.runtime_info.network);
+    let mut ifaces_selection = SelectView::new().popup().with_all(
+        ifaces
+            .iter()
+            .map(|iface| (iface.render(), iface.name.clone())),
+    );
+
+    // interfaces are already sorted by name, to always have a stable view
     let selected = ifaces_selection
         .iter()
         .position(|(_label, iface)| *iface == options.ifname)
-        .unwrap_or(ifaces.len() - 1);
+        .unwrap_or(ifaces.len().saturating_sub(1));
 
     ifaces_selection.set_selection(selected);
 
     let inner = FormView::new()
         .child("Management interface", ifaces_selection)
+        .child(
+            "VLAN tag (optional)",
+            EditView::new().content(
+                options
+                    .vlan_tag
+                    .map(|tag| tag.to_string())
+                    .unwrap_or_default(),
+            ),
+        )
         .child(
             "Hostname (FQDN)",
             EditView::new().content(options.fqdn.to_string()),
@@ -510,67 +724,151 @@ fn network_dialog(siv: &mut Cursive) -> InstallerView {
             EditView::new().content(options.gateway.to_string()),
         )
         .child(
-            "DNS server address",
-            EditView::new().content(options.dns_server.to_string()),
+            "Dual-stack IP address (optional)",
+            EditView::new().content(
+                options
+                    .dual_stack
+                    .as_ref()
+                    .map(|d| d.address.to_string())
+                    .unwrap_or_default(),
+            ),
+        )
+        .child(
+            "Dual-stack gateway (optional)",
+            EditView::new().content(
+                options
+                    .dual_stack
+                    .as_ref()
+                    .map(|d| d.gateway.to_string())
+                    .unwrap_or_default(),
+            ),
+        )
+        .child(
+            "DNS server addresses",
+            EditView::new().content(
+                options
+                    .dns_servers
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+        )
+        .child(
+            "DNS search domain (optional)",
+            EditView::new().content(options.search_domain.clone().unwrap_or_default()),
         )
         .with_name("network-options");
 
+    // The defaults still contain the unspecified address if DHCP did not provide one
+    let mut view = LinearLayout::vertical();
+    if options.address.is_unspecified() {
+        view.add_child(TextView::new(
+            "No IP address could be obtained via DHCP, please configure the network manually.",
+        ));
+        view.add_child(DummyView);
+    }
+    view.add_child(inner);
+
     InstallerView::new(
         state,
-        inner,
+        view,
         Box::new(|siv| {
             let options = siv.call_on_name("network-options", |view: &mut FormView| {
                 let ifname = view
                     .get_value::<SelectView, _>(0)
                     .ok_or("failed to retrieve management interface name")?;
 
-                let fqdn = view
+                let vlan_tag = view
                     .get_value::<EditView, _>(1)
+                    .ok_or("failed to retrieve VLAN tag")?;
+                let vlan_tag = match vlan_tag.trim() {
+                    "" => None,
+                    tag => Some(
+                        tag.parse::<u16>()
+                            .map_err(|err| format!("VLAN tag '{tag}': {err}"))?,
+                    ),
+                };
+
+                let fqdn = view
+                    .get_value::<EditView, _>(2)
                     .ok_or("failed to retrieve host FQDN")?
                     .parse::<Fqdn>()
                     .map_err(|err| format!("hostname does not look valid:\n\n{err}"))?;
 
                 let address = view
-                    .get_value::<CidrAddressEditView, _>(2)
+                    .get_value::<CidrAddressEditView, _>(3)
                     .ok_or("failed to retrieve host address")?;
 
                 let gateway = view
-                    .get_value::<EditView, _>(3)
-                    .ok_or("failed to retrieve gateway address")?
-                    .parse::<IpAddr>()
-                    .map_err(|err| err.to_string())?;
-
-                let dns_server = view
                     .get_value::<EditView, _>(4)
-                    .ok_or("failed to retrieve DNS server address")?
+                    .ok_or("failed to retrieve gateway address")?
                     .parse::<IpAddr>()
                     .map_err(|err| err.to_string())?;
 
-                if address.addr().is_ipv4() != gateway.is_ipv4() {
-                    Err("host and gateway IP address version must not differ".to_owned())
-                } else if address.addr().is_ipv4() != dns_server.is_ipv4() {
-                    Err("host and DNS IP address version must not differ".to_owned())
-                } else if fqdn.to_string().ends_with(".invalid") {
-                    Err("hostname does not look valid".to_owned())
-                } else {
-                    Ok(NetworkOptions {
-                        ifname,
-                        fqdn,
-                        address,
-                        gateway,
-                        dns_server,
+                let dual_stack_address = view
+                    .get_value::<EditView, _>(5)
+                    .ok_or("failed to retrieve dual-stack address")?;
+                let dual_stack_gateway = view
+                    .get_value::<EditView, _>(6)
+                    .ok_or("failed to retrieve dual-stack gateway")?;
+                let dual_stack = match (dual_stack_address.trim(), dual_stack_gateway.trim()) {
+                    ("", "") => None,
+                    (_, "") => return Err("dual-stack gateway must be set".to_owned()),
+                    ("", _) => return Err("dual-stack IP address must be set".to_owned()),
+                    (address, gateway) => Some(DualStackAddress {
+                        address: address
+                            .parse::<CidrAddress>()
+                            .map_err(|err| format!("dual-stack IP address: {err}"))?,
+                        gateway: gateway
+                            .parse::<IpAddr>()
+                            .map_err(|err| format!("dual-stack gateway: {err}"))?,
+                        routed_gateway: false,
+                    }),
+                };
+
+                let dns_servers = view
+                    .get_value::<EditView, _>(7)
+                    .ok_or("failed to retrieve DNS server addresses")?
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| {
+                        s.parse::<IpAddr>()
+                            .map_err(|err| format!("DNS server '{s}': {err}"))
                     })
+                    .collect::<Result<Vec<IpAddr>, String>>()?;
+
+                // If left empty, the domain of the FQDN is used, see
+                // NetworkOptions::resolved_search_domain()
+                let search_domain = view
+                    .get_value::<EditView, _>(8)
+                    .ok_or("failed to retrieve DNS search domain")?;
+                let search_domain = match search_domain.trim() {
+                    "" => None,
+                    domain => Some(domain.to_owned()),
+                };
+
+                if fqdn.to_string().ends_with(".invalid") {
+                    return Err("hostname does not look valid".to_owned());
                 }
+
+                let options = NetworkOptions {
+                    ifname,
+                    vlan_tag,
+                    fqdn,
+                    address,
+                    gateway,
+                    dns_servers,
+                    dual_stack,
+                    routed_gateway: false,
+                    search_domain,
+                };
+
+                Ok(options)
             });
 
             match options {
-                Some(Ok(options)) => {
-                    siv.with_user_data(|state: &mut InstallerState| {
-                        state.options.network = options;
-                    });
-
-                    switch_to_next_screen(siv, InstallerStep::Summary, &summary_dialog);
-                }
+                Some(Ok(options)) => network_dialog_check_gateway(siv, options),
                 Some(Err(err)) => siv.add_layer(Dialog::info(format!("Invalid values: {err}"))),
                 _ => siv.add_layer(Dialog::info("Invalid values")),
             }
@@ -579,25 +877,105 @@ fn network_dialog(siv: &mut Cursive) -> InstallerView {
     )
 }
 
+/// Validates the network options, asking for an explicit confirmation before accepting a gateway
+/// outside of the host network, as needed for routed setups. For dual-stack setups, each of the
+/// two gateways needs to be confirmed separately.
+fn network_dialog_check_gateway(siv: &mut Cursive, options: NetworkOptions) {
+    match options.validate() {
+        Ok(()) => network_dialog_next(siv, options),
+        Err(NetworkValidationError::GatewayNotInSubnet(gateway)) => {
+            // Both gateways are of different address families, thus this is unambiguous
+            let is_dual_stack = options
+                .dual_stack
+                .as_ref()
+                .is_some_and(|dual_stack| dual_stack.gateway == gateway);
+            let network = match &options.dual_stack {
+                Some(dual_stack) if is_dual_stack => &dual_stack.address,
+                _ => &options.address,
+            };
+            let message = format!(
+                "The gateway {gateway} is not part of the host network {}/{}.\n\n\
+                 This only works for routed setups, where the gateway is reachable on-link. \
+                 Otherwise, the host will not be reachable after the installation.\n\n\
+                 Use this gateway anyway?",
+                network.network_addr(),
+                network.mask(),
+            );
+
+            prompt_dialog(
+                siv,
+                "Gateway outside host network",
+                &message,
+                "Use anyway",
+                Box::new(move |siv| {
+                    let mut options = options.clone();
+                    match &mut options.dual_stack {
+                        Some(dual_stack) if is_dual_stack => dual_stack.routed_gateway = true,
+                        _ => options.routed_gateway = true,
+                    }
+                    // Asks again if the other gateway is outside its host network, too
+                    network_dialog_check_gateway(siv, options);
+                }),
+                "Cancel",
+                Box::new(|_| {}),
+            );
+        }
+        Err(err) => siv.add_layer(Dialog::info(format!("Invalid values: {err}"))),
+    }
+}
+
+fn network_dialog_next(siv: &mut Cursive, options: NetworkOptions) {
+    siv.with_user_data(|state: &mut InstallerState| {
+        state.options.network = options;
+    });
+
+    switch_to_next_screen(siv, InstallerStep::Summary, &summary_dialog);
+}
+
 pub struct SummaryOption {
-    name: &'static str,
+    name: Cow<'static, str>,
     value: String,
+    warning: Option<String>,
 }
 
 impl SummaryOption {
     pub fn new<S: Into<String>>(name: &'static str, value: S) -> Self {
         Self {
-            name,
+            name: Cow::Borrowed(name),
+            value: value.into(),
+            warning: None,
+        }
+    }
+
+    /// Creates an entry which is shown indented below the previous one, e.g. as part of a
+    /// breakdown.
+    pub fn nested<S: Into<String>>(name: &str, value: S) -> Self {
+        Self {
+            name: Cow::Owned(format!("  {name}")),
             value: value.into(),
+            warning: None,
         }
     }
+
+    /// Flags this entry as a risky, but still accepted, choice.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - Short explanation shown next to the value
+    pub fn with_warning<S: Into<String>>(mut self, reason: S) -> Self {
+        self.warning = Some(reason.into());
+        self
+    }
 }
 
 impl TableViewItem for SummaryOption {
     fn get_column(&self, name: &str) -> String {
         match name {
-            "name" => self.name.to_owned(),
-            "value" => self.value.clone(),
+            "name" => self.name.to_string(),
+            "value" => match &self.warning {
+                Some(reason) => format!("{} (warning: {reason})", self.value),
+                None => self.value.clone(),
+            },
             _ => unreachable!(),
         }
     }
@@ -613,6 +991,42 @@ fn summary_dialog(siv: &mut Cursive) -> InstallerView {
         .child(Button::new("Previous", switch_to_prev_screen))
         .child(DummyView)
         .child(Button::new("Install", |siv| {
+            let state = siv.user_data::<InstallerState>().unwrap();
+            let options = state.options.clone();
+
+            // Check everything once more, as the options might also have been pre-baked
+            let result =
+                InstallerOptionsBuilder::new(state.setup_info.config.product, &state.locales)
+                    .bootdisk(options.bootdisk)
+                    .timezone(options.timezone)
+                    .password(options.password)
+                    .network(options.network)
+                    .ntp(options.ntp)
+                    .proxy(options.proxy)
+                    .autoreboot(options.autoreboot)
+                    .build();
+
+            match result {
+                Ok(_) => {}
+                Err(BuildError::Invalid(errors)) => {
+                    let errors = errors
+                        .iter()
+                        .map(|err| format!("  * {err}"))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    siv.add_layer(Dialog::info(format!(
+                        "Cannot start the installation, please fix the following:\n\n{errors}"
+                    )));
+                    return;
+                }
+                Err(err) => {
+                    siv.add_layer(Dialog::info(format!(
+                        "Cannot start the installation, {err}"
+                    )));
+                    return;
+                }
+            }
+
             switch_to_next_screen(siv, InstallerStep::Install, &install_progress_dialog);
         }));
 
@@ -638,7 +1052,11 @@ fn summary_dialog(siv: &mut Cursive) -> InstallerView {
                     ("name".to_owned(), "Option".to_owned()),
                     ("value".to_owned(), "Selected value".to_owned()),
                 ])
-                .items(state.options.to_summary(&state.locales)),
+                .items(state.options.to_summary(
+                    &state.locales,
+                    state.setup_info.config.product,
+                    state.runtime_info.total_memory,
+                )),
         ))
         .child(
             LinearLayout::horizontal()
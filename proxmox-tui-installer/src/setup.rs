@@ -2,17 +2,28 @@ use std::collections::BTreeMap;
 
 use crate::options::InstallerOptions;
 use proxmox_installer_common::{
-    options::AdvancedBootdiskOptions,
-    setup::{InstallConfig, InstallFirstBootSetup, InstallRootPassword},
+    options::{AdvancedBootdiskOptions, FsType, NetworkValidationError},
+    setup::{InstallConfig, InstallFirstBootSetup, InstallRootPassword, InstallZfsOption},
 };
 
-impl From<InstallerOptions> for InstallConfig {
-    fn from(options: InstallerOptions) -> Self {
+impl TryFrom<InstallerOptions> for InstallConfig {
+    type Error = NetworkValidationError;
+
+    fn try_from(options: InstallerOptions) -> Result<Self, Self::Error> {
+        let dns = options
+            .network
+            .primary_dns()
+            .ok_or(NetworkValidationError::NoDnsServer)?;
+
         let mut config = Self {
             autoreboot: options.autoreboot as usize,
 
             filesys: options.bootdisk.fstype,
             hdsize: 0.,
+            esp_size: options
+                .bootdisk
+                .esp_size
+                .filter(|_| options.bootdisk.boot_mode.is_uefi()),
             swapsize: None,
             maxroot: None,
             minfree: None,
@@ -22,10 +33,11 @@ impl From<InstallerOptions> for InstallConfig {
             target_hd: None,
             disk_selection: BTreeMap::new(),
             existing_storage_auto_rename: 0,
+            lvm_encryption: None,
 
             country: options.timezone.country,
             timezone: options.timezone.timezone,
-            keymap: options.timezone.kb_layout,
+            keymap: options.timezone.kb_layout.to_string(),
 
             root_password: InstallRootPassword {
                 plain: Some(options.password.root_password),
@@ -35,15 +47,25 @@ impl From<InstallerOptions> for InstallConfig {
             root_ssh_keys: vec![],
 
             mngmt_nic: options.network.ifname,
+            mngmt_vlan: options.network.vlan_tag,
 
-            // Safety: At this point, it is know that we have a valid FQDN, as
-            // this is set by the TUI network panel, which only lets the user
-            // continue if a valid FQDN is provided.
-            hostname: options.network.fqdn.host().expect("valid FQDN").to_owned(),
+            hostname: options.network.fqdn.host().to_owned(),
             domain: options.network.fqdn.domain(),
             cidr: options.network.address,
             gateway: options.network.gateway,
-            dns: options.network.dns_server,
+            dual_stack_cidr: options
+                .network
+                .dual_stack
+                .as_ref()
+                .map(|d| d.address.clone()),
+            dual_stack_gateway: options.network.dual_stack.as_ref().map(|d| d.gateway),
+            dns,
+            secondary_dns: options.network.dns_servers.into_iter().skip(1).collect(),
+            search_domain: options.network.search_domain,
+
+            ntp_servers: options.ntp.servers,
+            use_dhcp_ntp: options.ntp.use_dhcp_ntp,
+            http_proxy: options.proxy.map(|proxy| proxy.to_url()),
 
             first_boot: InstallFirstBootSetup::default(),
         };
@@ -51,20 +73,27 @@ impl From<InstallerOptions> for InstallConfig {
         match &options.bootdisk.advanced {
             AdvancedBootdiskOptions::Lvm(lvm) => {
                 config.hdsize = lvm.total_size;
-                config.target_hd = Some(options.bootdisk.disks[0].path.clone());
-                config.swapsize = lvm.swap_size;
-                config.maxroot = lvm.max_root_size;
-                config.minfree = lvm.min_lvm_free;
-                config.maxvz = lvm.max_data_size;
+                config.target_hd = Some(options.bootdisk.disks[0].stable_path().to_owned());
+                config.swapsize = lvm.resolved_swap_size();
+                config.maxroot = lvm.resolved_max_root_size();
+                config.minfree = lvm.resolved_min_lvm_free();
+                config.maxvz = lvm.resolved_max_data_size();
+                config.lvm_encryption = lvm.encryption.clone();
             }
             AdvancedBootdiskOptions::Zfs(zfs) => {
                 config.hdsize = zfs.disk_size;
-                config.zfs_opts = Some(zfs.clone().into());
+                config.zfs_opts = Some(InstallZfsOption {
+                    draid_spec: match options.bootdisk.fstype {
+                        FsType::Zfs(level) => zfs.draid_spec(level, options.bootdisk.disks.len()),
+                        _ => None,
+                    },
+                    ..zfs.clone().into()
+                });
 
                 for (i, disk) in options.bootdisk.disks.iter().enumerate() {
                     config
                         .disk_selection
-                        .insert(i.to_string(), disk.index.clone());
+                        .insert(i.to_string(), disk.stable_path().to_owned());
                 }
             }
             AdvancedBootdiskOptions::Btrfs(btrfs) => {
@@ -74,11 +103,11 @@ impl From<InstallerOptions> for InstallConfig {
                 for (i, disk) in options.bootdisk.disks.iter().enumerate() {
                     config
                         .disk_selection
-                        .insert(i.to_string(), disk.index.clone());
+                        .insert(i.to_string(), disk.stable_path().to_owned());
                 }
             }
         }
 
-        config
+        Ok(config)
     }
 }
@@ -1,45 +1,79 @@
 use std::{
     marker::PhantomData,
+    ops::RangeInclusive,
     sync::{Arc, Mutex},
 };
 
 use cursive::{
     view::{Nameable, Resizable, ViewWrapper},
     views::{
-        Button, Dialog, DummyView, LinearLayout, NamedView, PaddedView, Panel, ScrollView,
-        SelectView, TextView, ViewRef,
+        Button, Checkbox, Dialog, DummyView, EditView, LinearLayout, NamedView, PaddedView, Panel,
+        ScrollView, SelectView, TextView, ViewRef,
     },
     Cursive, Vec2, View,
 };
 
-use super::{DiskSizeEditView, FormView, IntegerEditView, TabbedView};
+use super::{DiskSizeEditView, FormView, IntegerEditView, LvmSizeEditView, TabbedView};
 use crate::options::FS_TYPES;
 use crate::InstallerState;
 
 use proxmox_installer_common::{
-    disk_checks::{
-        check_btrfs_raid_config, check_disks_4kn_legacy_boot, check_for_duplicate_disks,
-        check_zfs_raid_config,
-    },
+    disk_checks::check_disks_4kn_legacy_boot,
     options::{
-        AdvancedBootdiskOptions, BootdiskOptions, BtrfsBootdiskOptions, Disk, FsType,
-        LvmBootdiskOptions, ZfsBootdiskOptions, BTRFS_COMPRESS_OPTIONS, ZFS_CHECKSUM_OPTIONS,
-        ZFS_COMPRESS_OPTIONS,
+        disk_order_by_kind, disks_matching_size, sort_disks_by_size, AdvancedBootdiskOptions,
+        BootMode, BootdiskOptions, BtrfsBootdiskOptions, DataVolumeMode, Disk, DiskEncryption,
+        FsType, LvmBootdiskOptions, LvmSize, LvmSizeField, LvmValidationError, ZfsBootdiskOptions,
+        ZfsEncryption, BTRFS_COMPRESS_OPTIONS, ESP_SIZE_RANGE, ZFS_CHECKSUM_OPTIONS,
+        ZFS_COMPRESS_OPTIONS, ZFS_DRAID_DEFAULT_DATA, ZFS_ENCRYPTION_OPTIONS,
     },
-    setup::{BootType, ProductConfig, ProxmoxProduct, RuntimeInfo},
+    setup::{self, BootType, ProductConfig, ProxmoxProduct, RuntimeInfo},
+    utils::format_bytes,
 };
 
 /// OpenZFS specifies 64 MiB as the absolute minimum:
 /// <https://openzfs.github.io/openzfs-docs/Performance%20and%20Tuning/Module%20Parameters.html#zfs-arc-max>
 const ZFS_ARC_MIN_SIZE_MIB: usize = 64; // MiB
 
+/// Valid range for the ZFS `ashift` pool property, i.e. 512 B up to 64 KiB sectors.
+const ZFS_ASHIFT_RANGE: RangeInclusive<usize> = 9..=16;
+
+/// Valid range for the ZFS `copies` dataset property.
+const ZFS_COPIES_RANGE: RangeInclusive<usize> = 1..=3;
+
+/// Name of the [`TextView`] showing the estimated usable space of RAID setups.
+const USABLE_SPACE_VIEW_ID: &str = "multidisk-usable-space";
+
 /// Convenience wrapper when needing to take a (interior-mutable) reference to `BootdiskOptions`.
 pub type BootdiskOptionsRef = Arc<Mutex<BootdiskOptions>>;
 
+/// Advanced options last used for each kind of filesystem during this session, such that
+/// switching the filesystem type back and forth in the advanced dialog keeps all adjustments.
+///
+/// ext4 and XFS share the same LVM options, which are only re-applied for the same target disk.
+/// Everything is discarded if the set of available disks changes, as the selected disks of ZFS
+/// and Btrfs setups are stored as indices.
+#[derive(Clone, Default)]
+pub struct AdvancedBootdiskOptionsCache {
+    lvm: Option<(Disk, LvmBootdiskOptions)>,
+    zfs: Option<ZfsBootdiskOptions>,
+    btrfs: Option<BtrfsBootdiskOptions>,
+}
+
+impl AdvancedBootdiskOptionsCache {
+    /// Returns the cached LVM options, if they were set up for the given target disk.
+    fn lvm_for(&self, disk: &Disk) -> Option<&LvmBootdiskOptions> {
+        self.lvm
+            .as_ref()
+            .filter(|(cached, _)| cached == disk)
+            .map(|(_, options)| options)
+    }
+}
+
 pub struct BootdiskOptionsView {
     view: LinearLayout,
     advanced_options: BootdiskOptionsRef,
     boot_type: BootType,
+    product: ProxmoxProduct,
 }
 
 impl BootdiskOptionsView {
@@ -50,7 +84,7 @@ impl BootdiskOptionsView {
             .child(
                 "Target harddisk",
                 target_bootdisk_selectview(
-                    &runinfo.disks,
+                    runinfo,
                     advanced_options.clone(),
                     // At least one disk must always exist to even get to this point,
                     // see proxmox_installer_common::setup::installer_setup()
@@ -63,9 +97,15 @@ impl BootdiskOptionsView {
             .user_data::<InstallerState>()
             .map(|state| state.setup_info.config.clone())
             .unwrap(); // Safety: InstallerState must always be set
+        let product = product_conf.product;
 
         let advanced_button = LinearLayout::horizontal()
             .child(DummyView.full_width())
+            .child(Button::new("Rescan disks", {
+                let options = advanced_options.clone();
+                move |siv| Self::rescan_disks(siv, options.clone())
+            }))
+            .child(DummyView)
             .child(Button::new("Advanced options", {
                 let runinfo = runinfo.clone();
                 let options = advanced_options.clone();
@@ -79,6 +119,7 @@ impl BootdiskOptionsView {
                     view.layout(siv.screen_size());
 
                     siv.add_layer(view);
+                    AdvancedBootdiskOptionsView::update_usable_space(siv);
                 }
             }));
 
@@ -96,6 +137,7 @@ impl BootdiskOptionsView {
             view,
             advanced_options,
             boot_type,
+            product,
         }
     }
 
@@ -104,9 +146,51 @@ impl BootdiskOptionsView {
         // info on submit directly to the shared `BootdiskOptionsRef` - so just clone() + return
         // it.
         let options = self.advanced_options.lock().unwrap().clone();
+        options
+            .validate(self.product)
+            .map_err(|err| err.to_string())?;
         check_disks_4kn_legacy_boot(self.boot_type, &options.disks)?;
         Ok(options)
     }
+
+    /// Re-probes the available disks and rebuilds the view with them. The current selection is
+    /// kept if all selected disks are still present, otherwise the options are reset.
+    fn rescan_disks(siv: &mut Cursive, options_ref: BootdiskOptionsRef) {
+        let state = siv.user_data::<InstallerState>().unwrap();
+
+        let disks = match setup::rescan_disks(state.in_test_mode) {
+            Ok(disks) => disks,
+            Err(err) => {
+                siv.add_layer(Dialog::info(format!("Failed to rescan disks: {err}")));
+                return;
+            }
+        };
+
+        let mut options = options_ref.lock().unwrap().clone();
+        let preserved = options.update_disks(&disks, state.runtime_info.total_memory);
+        let disk_count = disks.len();
+
+        if state.runtime_info.disks != disks {
+            state.bootdisk_cache = Default::default();
+        }
+        state.runtime_info.disks = disks;
+        state.options.bootdisk = options.clone();
+        // Disk indices might refer to different disks now
+        state.acked_disk_size_warning = None;
+
+        let runinfo = state.runtime_info.clone();
+        let view = Self::new(siv, &runinfo, &options);
+        siv.call_on_name("bootdisk-options", |v: &mut BootdiskOptionsView| *v = view);
+
+        siv.add_layer(Dialog::info(if preserved {
+            format!("Found {disk_count} disk(s).")
+        } else {
+            format!(
+                "Found {disk_count} disk(s). The previously selected disk(s) are no longer \
+                 available, the bootdisk options have been reset."
+            )
+        }));
+    }
 }
 
 impl ViewWrapper for BootdiskOptionsView {
@@ -115,9 +199,18 @@ impl ViewWrapper for BootdiskOptionsView {
 
 struct AdvancedBootdiskOptionsView {
     view: LinearLayout,
+    boot_mode: BootMode,
+    /// ESP size to keep if the field is not shown, i.e. on legacy BIOS systems
+    esp_size: Option<usize>,
 }
 
 impl AdvancedBootdiskOptionsView {
+    /// Index of the form with the filesystem type and ESP size in [`Self::view`].
+    const FORM_VIEW_INDEX: usize = 1;
+    /// Index of the filesystem-specific options view in [`Self::view`], following the form and
+    /// a spacer.
+    const FS_OPTIONS_VIEW_INDEX: usize = 3;
+
     fn new(
         runinfo: &RuntimeInfo,
         options_ref: BootdiskOptionsRef,
@@ -149,9 +242,27 @@ impl AdvancedBootdiskOptionsView {
                 }
             });
 
+        // The ESP size applies to all filesystems, but only matters when booted via UEFI. Like
+        // for the ZFS ARC size, the default is only used as placeholder, so that the low-level
+        // installer picks the size itself if the value is never modified by the user.
+        let esp_size_view = {
+            let view = IntegerEditView::new()
+                .placeholder(options.effective_esp_size(&options.disks[0]))
+                .max_value(*ESP_SIZE_RANGE.end());
+
+            match options.esp_size {
+                Some(size) => view.content(size),
+                None => view,
+            }
+        };
+
+        let form = FormView::new()
+            .child("Filesystem", fstype_select)
+            .child_conditional(options.boot_mode.is_uefi(), "ESP size (MiB)", esp_size_view);
+
         let mut view = LinearLayout::vertical()
             .child(DummyView.full_width())
-            .child(FormView::new().child("Filesystem", fstype_select))
+            .child(form)
             .child(DummyView.full_width());
 
         // Create the appropriate (inner) advanced options view
@@ -159,6 +270,7 @@ impl AdvancedBootdiskOptionsView {
             AdvancedBootdiskOptions::Lvm(lvm) => view.add_child(LvmBootdiskOptionsView::new(
                 &options.disks[0],
                 lvm,
+                runinfo.total_memory,
                 &product_conf,
             )),
             AdvancedBootdiskOptions::Zfs(zfs) => {
@@ -169,7 +281,11 @@ impl AdvancedBootdiskOptionsView {
             }
         };
 
-        Self { view }
+        Self {
+            view,
+            boot_mode: options.boot_mode,
+            esp_size: options.esp_size,
+        }
     }
 
     /// Called when a new filesystem type is chosen by the user.
@@ -182,12 +298,14 @@ impl AdvancedBootdiskOptionsView {
     /// # Arguments
     /// * `siv` - Cursive instance
     /// * `fstype` - The chosen filesystem type by the user, for which the UI should be
-    ///              updated accordingly
+    ///   updated accordingly
     /// * `options_ref` - [`BootdiskOptionsRef`] where advanced disk options should be saved to
     fn fstype_on_submit(siv: &mut Cursive, fstype: &FsType, options_ref: BootdiskOptionsRef) {
         let state = siv.user_data::<InstallerState>().unwrap();
         let runinfo = state.runtime_info.clone();
         let product_conf = state.setup_info.config.clone();
+        let mut cache = state.bootdisk_cache.clone();
+        let avail_disks = runinfo.disks.len();
 
         // Only used for LVM configurations, ZFS and Btrfs do not use the target disk selector
         // Must be done here, as we cannot mutable borrow `siv` a second time inside the closure
@@ -196,30 +314,62 @@ impl AdvancedBootdiskOptionsView {
             .find_name::<FormView>("bootdisk-options-target-disk")
             .and_then(|v| v.get_value::<SelectView<Disk>, _>(0))
             // If not defined, then the view was switched from a non-LVM filesystem to a LVM one.
-            // Just use the first disk is such a case.
-            .unwrap_or_else(|| runinfo.disks[0].clone());
+            // Use the first disk of the previous selection in such a case, or just the first
+            // available disk if there is none.
+            .unwrap_or_else(|| {
+                options_ref
+                    .lock()
+                    .unwrap()
+                    .disks
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| runinfo.disks[0].clone())
+            });
 
         // Update the (inner) options view
         let screen_size = siv.screen_size();
         siv.call_on_name("advanced-bootdisk-options-dialog", |view: &mut Dialog| {
-            if let Some(AdvancedBootdiskOptionsView { view }) =
-                view.get_content_mut().downcast_mut()
+            if let Some(advanced) = view
+                .get_content_mut()
+                .downcast_mut::<AdvancedBootdiskOptionsView>()
             {
-                view.remove_child(3);
+                advanced.cache_current(&mut cache);
+
+                let view = &mut advanced.view;
+                view.remove_child(Self::FS_OPTIONS_VIEW_INDEX);
                 match fstype {
-                    FsType::Ext4 | FsType::Xfs => {
-                        view.add_child(LvmBootdiskOptionsView::new_with_defaults(
+                    FsType::Ext4 | FsType::Xfs => match cache.lvm_for(&selected_lvm_disk) {
+                        Some(lvm) => view.add_child(LvmBootdiskOptionsView::new(
                             &selected_lvm_disk,
+                            lvm,
+                            runinfo.total_memory,
                             &product_conf,
-                        ))
-                    }
-                    FsType::Zfs(_) => view.add_child(ZfsBootdiskOptionsView::new_with_defaults(
-                        &runinfo,
-                        &product_conf,
-                    )),
-                    FsType::Btrfs(_) => {
-                        view.add_child(BtrfsBootdiskOptionsView::new_with_defaults(&runinfo))
-                    }
+                        )),
+                        None => view.add_child(LvmBootdiskOptionsView::new_with_defaults(
+                            &selected_lvm_disk,
+                            runinfo.total_memory,
+                            &product_conf,
+                        )),
+                    },
+                    FsType::Zfs(_) => match &cache.zfs {
+                        Some(zfs) => view.add_child(ZfsBootdiskOptionsView::new(
+                            &runinfo,
+                            zfs,
+                            &product_conf,
+                        )),
+                        None => view.add_child(ZfsBootdiskOptionsView::new_with_defaults(
+                            &runinfo,
+                            &product_conf,
+                        )),
+                    },
+                    FsType::Btrfs(_) => match &cache.btrfs {
+                        Some(btrfs) => {
+                            view.add_child(BtrfsBootdiskOptionsView::new(&runinfo, btrfs))
+                        }
+                        None => {
+                            view.add_child(BtrfsBootdiskOptionsView::new_with_defaults(&runinfo))
+                        }
+                    },
                 }
 
                 // Pre-compute the child's layout, since it might depend on the size. Without this,
@@ -229,6 +379,10 @@ impl AdvancedBootdiskOptionsView {
             }
         });
 
+        if let Some(state) = siv.user_data::<InstallerState>() {
+            state.bootdisk_cache = cache;
+        }
+
         // The "bootdisk-options-target-disk" view might be either a `SelectView` (if ext4 of XFS
         // is used) or a label containing the filesytem/RAID type (for ZFS and Btrfs).
         // Now, unconditionally replace it with the appropriate type of these two, depending on the
@@ -239,64 +393,162 @@ impl AdvancedBootdiskOptionsView {
                 FsType::Ext4 | FsType::Xfs => {
                     view.replace_child(
                         0,
-                        target_bootdisk_selectview(&runinfo.disks, options_ref, &selected_lvm_disk),
+                        target_bootdisk_selectview(&runinfo, options_ref, &selected_lvm_disk),
                     );
                 }
                 other => view.replace_child(0, TextView::new(other.to_string())),
             },
         );
+
+        // Let the user know right away if the chosen RAID level cannot be satisfied at all,
+        // instead of only failing once the options are submitted.
+        let min_disks = fstype.get_min_disks();
+        if avail_disks < min_disks {
+            siv.add_layer(Dialog::info(format!(
+                "{fstype} needs at least {min_disks} disks, but only {avail_disks} \
+                 disk(s) were found in this system."
+            )));
+        }
+
+        Self::update_usable_space(siv);
+    }
+
+    /// Saves the options of the currently shown filesystem-specific view to the cache, such that
+    /// they can be restored when switching back to it. Invalid options are not saved.
+    ///
+    /// # Arguments
+    /// * `cache` - Cache to save the options to
+    fn cache_current(&mut self, cache: &mut AdvancedBootdiskOptionsCache) {
+        let Some(view) = self.view.get_child_mut(Self::FS_OPTIONS_VIEW_INDEX) else {
+            return;
+        };
+
+        if let Some(view) = view.downcast_mut::<LvmBootdiskOptionsView>() {
+            if let Ok(lvm) = view.get_values() {
+                cache.lvm = Some(lvm);
+            }
+        } else if let Some(view) = view.downcast_mut::<ZfsBootdiskOptionsView>() {
+            if let Ok((_, zfs)) = view.get_values() {
+                cache.zfs = Some(zfs);
+            }
+        } else if let Some(view) = view.downcast_mut::<BtrfsBootdiskOptionsView>() {
+            if let Some((_, btrfs)) = view.get_values() {
+                cache.btrfs = Some(btrfs);
+            }
+        }
+    }
+
+    /// Updates the estimated usable space shown below the disk selection of ZFS and Btrfs
+    /// setups, see [`BootdiskOptions::estimated_usable_size`]. Does nothing for LVM setups.
+    ///
+    /// # Arguments
+    /// * `siv` - Cursive instance
+    fn update_usable_space(siv: &mut Cursive) {
+        let options = siv
+            .call_on_name("advanced-bootdisk-options-dialog", |view: &mut Dialog| {
+                let view = view.get_content_mut().downcast_mut::<Self>()?;
+
+                // Retrieving the LVM options would also validate them and move the focus
+                if view
+                    .view
+                    .get_child(Self::FS_OPTIONS_VIEW_INDEX)?
+                    .is::<LvmBootdiskOptionsView>()
+                {
+                    return None;
+                }
+
+                Some(view.get_values())
+            })
+            .flatten();
+
+        let Some(options) = options else {
+            return;
+        };
+
+        let size = match options.ok().and_then(|o| o.estimated_usable_size()) {
+            Some(size) => format_bytes((size * 1024. * 1024. * 1024.) as u64),
+            None => "-".to_owned(),
+        };
+
+        siv.call_on_name(USABLE_SPACE_VIEW_ID, |view: &mut TextView| {
+            view.set_content(format!("Estimated usable space: {size}"));
+        });
     }
 
     fn get_values(&mut self) -> Result<BootdiskOptions, String> {
-        let fstype = self
+        let form = self
             .view
-            .get_child(1)
+            .get_child(Self::FORM_VIEW_INDEX)
             .and_then(|v| v.downcast_ref::<FormView>())
-            .and_then(|v| v.get_value::<SelectView<FsType>, _>(0))
+            .ok_or("Failed to retrieve filesystem options".to_owned())?;
+
+        let fstype = form
+            .get_value::<SelectView<FsType>, _>(0)
             .ok_or("Failed to retrieve filesystem type".to_owned())?;
 
+        let esp_size = if self.boot_mode.is_uefi() {
+            form.get_child::<IntegerEditView>(1)
+                .and_then(|view| view.get_content_maybe().transpose().ok())
+                .ok_or("Failed to retrieve ESP size".to_owned())?
+        } else {
+            self.esp_size
+        };
+
         let advanced = self
             .view
-            .get_child_mut(3)
+            .get_child_mut(Self::FS_OPTIONS_VIEW_INDEX)
             .ok_or("Failed to retrieve advanced bootdisk options view".to_owned())?;
 
         if let Some(view) = advanced.downcast_mut::<LvmBootdiskOptionsView>() {
-            let (disk, advanced) = view
-                .get_values()
-                .ok_or("Failed to retrieve advanced bootdisk options")?;
+            let (disk, advanced) = view.get_values().inspect_err(|_| {
+                // Also move the focus to the LVM options, where the invalid field is focused
+                let _ = self.view.set_focus_index(Self::FS_OPTIONS_VIEW_INDEX); // ignore errors
+            })?;
 
             Ok(BootdiskOptions {
                 disks: vec![disk],
                 fstype,
                 advanced: AdvancedBootdiskOptions::Lvm(advanced),
+                boot_mode: self.boot_mode,
+                esp_size,
             })
         } else if let Some(view) = advanced.downcast_mut::<ZfsBootdiskOptionsView>() {
-            let (disks, advanced) = view
-                .get_values()
-                .ok_or("Failed to retrieve advanced bootdisk options")?;
+            let (disks, advanced) = view.get_values()?;
+
+            if !ZFS_ASHIFT_RANGE.contains(&advanced.ashift) {
+                return Err(format!(
+                    "ashift must be between {} and {}",
+                    ZFS_ASHIFT_RANGE.start(),
+                    ZFS_ASHIFT_RANGE.end()
+                ));
+            }
 
-            if let FsType::Zfs(level) = fstype {
-                check_zfs_raid_config(level, &disks).map_err(|err| format!("{fstype}: {err}"))?;
+            if !ZFS_COPIES_RANGE.contains(&advanced.copies) {
+                return Err(format!(
+                    "copies must be between {} and {}",
+                    ZFS_COPIES_RANGE.start(),
+                    ZFS_COPIES_RANGE.end()
+                ));
             }
 
             Ok(BootdiskOptions {
                 disks,
                 fstype,
                 advanced: AdvancedBootdiskOptions::Zfs(advanced),
+                boot_mode: self.boot_mode,
+                esp_size,
             })
         } else if let Some(view) = advanced.downcast_mut::<BtrfsBootdiskOptionsView>() {
             let (disks, advanced) = view
                 .get_values()
                 .ok_or("Failed to retrieve advanced bootdisk options")?;
 
-            if let FsType::Btrfs(level) = fstype {
-                check_btrfs_raid_config(level, &disks).map_err(|err| format!("{fstype}: {err}"))?;
-            }
-
             Ok(BootdiskOptions {
                 disks,
                 fstype,
                 advanced: AdvancedBootdiskOptions::Btrfs(advanced),
+                boot_mode: self.boot_mode,
+                esp_size,
             })
         } else {
             Err("Invalid bootdisk view state".to_owned())
@@ -311,72 +563,247 @@ impl ViewWrapper for AdvancedBootdiskOptionsView {
 struct LvmBootdiskOptionsView {
     view: FormView,
     disk: Disk,
+    product: ProxmoxProduct,
+    /// Total memory installed in the system in MiB, for the swap size if it is left unset
+    total_memory: usize,
     has_extra_fields: bool,
 }
 
 impl LvmBootdiskOptionsView {
-    fn new(disk: &Disk, options: &LvmBootdiskOptions, product_conf: &ProductConfig) -> Self {
+    // Indices of the fields in the form. The extra fields are only shown for PVE, see
+    // `Self::min_lvm_free_id()` for the fields following them.
+    const TOTAL_SIZE_ID: usize = 0;
+    const SWAP_SIZE_ID: usize = 1;
+    const MAX_ROOT_SIZE_ID: usize = 2;
+    const MAX_DATA_SIZE_ID: usize = 3;
+    const CREATE_DATA_VOLUME_ID: usize = 4;
+
+    fn new(
+        disk: &Disk,
+        options: &LvmBootdiskOptions,
+        total_memory: usize,
+        product_conf: &ProductConfig,
+    ) -> Self {
         let show_extra_fields = product_conf.product == ProxmoxProduct::PVE;
+        let max_data_size = match options.data_volume {
+            DataVolumeMode::Fixed(size) => Some(size),
+            DataVolumeMode::Auto | DataVolumeMode::None => None,
+        };
+        let passphrase = options
+            .encryption
+            .as_ref()
+            .map(|e| e.passphrase.clone())
+            .unwrap_or_default();
 
         let view = FormView::new()
             .child(
                 "Total size",
                 DiskSizeEditView::new()
                     .content(options.total_size)
-                    .max_value(options.total_size),
+                    .max_value(disk.size),
             )
             .child(
-                "Swap size",
-                DiskSizeEditView::new_emptyable().content_maybe(options.swap_size),
+                "Swap size (0 to disable)",
+                LvmSizeEditView::new().content_maybe(options.swap_size),
             )
             .child_conditional(
                 show_extra_fields,
                 "Maximum root volume size",
-                DiskSizeEditView::new_emptyable().content_maybe(options.max_root_size),
+                LvmSizeEditView::new().content_maybe(options.max_root_size),
             )
             .child_conditional(
                 show_extra_fields,
                 "Maximum data volume size",
-                DiskSizeEditView::new_emptyable().content_maybe(options.max_data_size),
+                // Disabling the data volume is done using the checkbox below instead
+                LvmSizeEditView::new().content_maybe(max_data_size),
+            )
+            .child_conditional(
+                show_extra_fields,
+                "Create thin pool for guests",
+                Checkbox::new().with_checked(options.data_volume != DataVolumeMode::None),
             )
             .child(
                 "Minimum free LVM space",
-                DiskSizeEditView::new_emptyable().content_maybe(options.min_lvm_free),
+                LvmSizeEditView::new().content_maybe(options.min_lvm_free),
+            )
+            // Leaving the passphrase empty disables encryption
+            .child(
+                "Encryption passphrase",
+                EditView::new().secret().content(passphrase.clone()),
+            )
+            .child(
+                "Confirm passphrase",
+                EditView::new().secret().content(passphrase),
+            )
+            .child(
+                "Unlock via TPM2",
+                Checkbox::new().with_checked(
+                    options
+                        .encryption
+                        .as_ref()
+                        .map(|e| e.tpm_unlock)
+                        .unwrap_or_default(),
+                ),
             );
 
         Self {
             view,
             disk: disk.clone(),
+            product: product_conf.product,
+            total_memory,
             has_extra_fields: show_extra_fields,
         }
     }
 
-    fn new_with_defaults(disk: &Disk, product_conf: &ProductConfig) -> Self {
-        Self::new(disk, &LvmBootdiskOptions::defaults_from(disk), product_conf)
+    fn new_with_defaults(disk: &Disk, total_memory: usize, product_conf: &ProductConfig) -> Self {
+        Self::new(
+            disk,
+            &LvmBootdiskOptions::defaults_from_with_memory(disk, total_memory),
+            total_memory,
+            product_conf,
+        )
     }
 
-    fn get_values(&mut self) -> Option<(Disk, LvmBootdiskOptions)> {
-        let min_lvm_free_id = if self.has_extra_fields { 4 } else { 2 };
+    /// Index of the minimum free LVM space field, which directly follows the swap size resp. the
+    /// extra fields if shown. It is followed by the encryption fields, see
+    /// [`Self::passphrase_id()`].
+    fn min_lvm_free_id(&self) -> usize {
+        if self.has_extra_fields {
+            Self::CREATE_DATA_VOLUME_ID + 1
+        } else {
+            Self::SWAP_SIZE_ID + 1
+        }
+    }
 
-        let max_root_size = self
-            .has_extra_fields
-            .then(|| self.view.get_value::<DiskSizeEditView, _>(2))
-            .flatten();
-        let max_data_size = self
-            .has_extra_fields
-            .then(|| self.view.get_value::<DiskSizeEditView, _>(3))
-            .flatten();
+    /// Index of the encryption passphrase field, which is followed by its confirmation and the
+    /// TPM2 unlock checkbox.
+    fn passphrase_id(&self) -> usize {
+        self.min_lvm_free_id() + 1
+    }
 
-        Some((
-            self.disk.clone(),
-            LvmBootdiskOptions {
-                total_size: self.view.get_value::<DiskSizeEditView, _>(0)?,
-                swap_size: self.view.get_value::<DiskSizeEditView, _>(1),
-                max_root_size,
-                max_data_size,
-                min_lvm_free: self.view.get_value::<DiskSizeEditView, _>(min_lvm_free_id),
+    fn get_values(&mut self) -> Result<(Disk, LvmBootdiskOptions), String> {
+        let passphrase_id = self.passphrase_id();
+        let confirm_id = passphrase_id + 1;
+        let tpm_unlock_id = passphrase_id + 2;
+
+        let (max_root_size, data_volume) = if self.has_extra_fields {
+            let max_root_size = self.get_lvm_size(Self::MAX_ROOT_SIZE_ID)?;
+            let create_data_volume = self
+                .view
+                .get_value::<Checkbox, _>(Self::CREATE_DATA_VOLUME_ID)
+                .ok_or("Failed to retrieve thin pool setting")?;
+
+            let data_volume = if create_data_volume {
+                match self.get_lvm_size(Self::MAX_DATA_SIZE_ID)? {
+                    Some(size) => DataVolumeMode::Fixed(size),
+                    None => DataVolumeMode::Auto,
+                }
+            } else {
+                DataVolumeMode::None
+            };
+
+            (max_root_size, data_volume)
+        } else {
+            (None, DataVolumeMode::Auto)
+        };
+
+        let mut options = LvmBootdiskOptions {
+            total_size: self
+                .view
+                .get_value::<DiskSizeEditView, _>(Self::TOTAL_SIZE_ID)
+                .ok_or("Failed to retrieve total size")?,
+            swap_size: self.get_lvm_size(Self::SWAP_SIZE_ID)?,
+            max_root_size,
+            data_volume,
+            min_lvm_free: None,
+            encryption: None,
+        };
+
+        // Unlike the validation below, this also ensures that enough space is left for the root
+        // volume
+        if let Some(min_lvm_free) = self.get_lvm_size(self.min_lvm_free_id())? {
+            let bytes = (min_lvm_free.resolve(options.total_size) * 1024. * 1024. * 1024.) as u64;
+            if let Err(err) = options.set_min_lvm_free(bytes, self.product, self.total_memory) {
+                self.focus_invalid_field(&err);
+                return Err(err.to_string());
+            }
+            // Keep the size as entered, e.g. as percentage
+            options.min_lvm_free = Some(min_lvm_free);
+        }
+
+        let passphrase = self
+            .view
+            .get_value::<EditView, _>(passphrase_id)
+            .ok_or("Failed to retrieve encryption passphrase")?;
+        let confirm = self
+            .view
+            .get_value::<EditView, _>(confirm_id)
+            .ok_or("Failed to retrieve encryption passphrase confirmation")?;
+
+        if passphrase != confirm {
+            self.view.focus_child(confirm_id);
+            return Err("Encryption passphrases do not match".to_owned());
+        }
+
+        let options = LvmBootdiskOptions {
+            encryption: (!passphrase.is_empty()).then(|| DiskEncryption {
+                passphrase,
+                tpm_unlock: self
+                    .view
+                    .get_value::<Checkbox, _>(tpm_unlock_id)
+                    .unwrap_or_default(),
+            }),
+            ..options
+        };
+
+        if let Err(err) = options.validate(&self.disk, self.product) {
+            self.focus_invalid_field(&err);
+            return Err(err.to_string());
+        }
+
+        Ok((self.disk.clone(), options))
+    }
+
+    /// Points the user to the field causing the given validation error.
+    fn focus_invalid_field(&mut self, err: &LvmValidationError) {
+        let min_lvm_free_id = self.min_lvm_free_id();
+
+        self.view.focus_child(match err {
+            LvmValidationError::TotalSizeTooBig { .. } => Self::TOTAL_SIZE_ID,
+            LvmValidationError::SwapRootTooBig { .. } => Self::SWAP_SIZE_ID,
+            LvmValidationError::RootTooSmall { .. } | LvmValidationError::RootTooBig { .. } => {
+                Self::MAX_ROOT_SIZE_ID
+            }
+            LvmValidationError::MinFreeTooBig { .. } | LvmValidationError::NoRoomForRoot { .. } => {
+                min_lvm_free_id
+            }
+            LvmValidationError::InvalidPercent { field, .. }
+            | LvmValidationError::Overcommitted { field, .. } => match field {
+                LvmSizeField::Swap => Self::SWAP_SIZE_ID,
+                LvmSizeField::MaxRoot => Self::MAX_ROOT_SIZE_ID,
+                LvmSizeField::MaxData => Self::MAX_DATA_SIZE_ID,
+                LvmSizeField::MinFree => min_lvm_free_id,
             },
-        ))
+            LvmValidationError::Encryption(_) => self.passphrase_id(),
+        });
+    }
+
+    /// Retrieves the value of one of the optional LVM size fields, focusing it if the value is
+    /// invalid.
+    ///
+    /// # Arguments
+    /// * `id` - Index of the field in the form
+    fn get_lvm_size(&mut self, id: usize) -> Result<Option<LvmSize>, String> {
+        let size = self
+            .view
+            .get_child::<LvmSizeEditView>(id)
+            .ok_or("Failed to retrieve LVM size")?
+            .get_content();
+
+        size.map_err(|err| {
+            self.view.focus_child(id);
+            err.to_string()
+        })
     }
 }
 
@@ -387,6 +814,8 @@ impl ViewWrapper for LvmBootdiskOptionsView {
 struct MultiDiskOptionsView<T> {
     view: LinearLayout,
     layout_data: Option<(Vec<Disk>, Vec<usize>, T)>,
+    /// Positions of the available disks in the order they are listed in each slot
+    disk_order: Vec<usize>,
     phantom: PhantomData<T>,
 }
 
@@ -397,6 +826,7 @@ impl<T: View> MultiDiskOptionsView<T> {
         Self {
             view: LinearLayout::vertical().child(DummyView).child(DummyView),
             layout_data: Some((avail_disks.to_vec(), selected_disks.to_vec(), options_view)),
+            disk_order: disk_order_by_kind(avail_disks),
             phantom: PhantomData,
         }
     }
@@ -463,27 +893,45 @@ impl<T: View> MultiDiskOptionsView<T> {
                 disks.push(disk);
             }
 
-            selected_disks.push(
-                disk_form
-                    .get_child::<SelectView<Option<Disk>>>(i)?
-                    .selected_id()?,
-            );
+            let selected_id = disk_form
+                .get_child::<SelectView<Option<Disk>>>(i)?
+                .selected_id()?;
+            selected_disks.push(self.selection_from_view_id(selected_id));
         }
 
         Some((disks, selected_disks))
     }
 
+    /// Maps the selection of a slot, i.e. the index of the disk or the number of available disks
+    /// for unused slots, to the index of the respective entry in the slot's [`SelectView`], where
+    /// the "do not use" entry comes first, followed by the disks as ordered by
+    /// [`disk_order_by_kind`].
+    fn view_id_from_selection(&self, selection: usize) -> usize {
+        self.disk_order
+            .iter()
+            .position(|i| *i == selection)
+            .map_or(0, |pos| pos + 1)
+    }
+
+    /// Inverse of [`Self::view_id_from_selection()`].
+    fn selection_from_view_id(&self, id: usize) -> usize {
+        id.checked_sub(1)
+            .and_then(|pos| self.disk_order.get(pos).copied())
+            .unwrap_or(self.disk_order.len())
+    }
+
     fn do_layout(&mut self, size: Vec2) {
         let Some((avail_disks, selected_disks, options_view)) = self.layout_data.take() else {
             panic!("cannot do layout without data!");
         };
 
-        let mut selectable_disks = avail_disks
-            .iter()
-            .map(|d| (d.to_string(), Some(d.clone())))
-            .collect::<Vec<(String, Option<Disk>)>>();
-
-        selectable_disks.push(("-- do not use --".to_owned(), None));
+        // Offer to leave a slot unused first, so that it is easy to find even with many disks
+        let mut selectable_disks: Vec<(String, Option<Disk>)> =
+            vec![("-- do not use --".to_owned(), None)];
+        selectable_disks.extend(self.disk_order.iter().map(|i| {
+            let disk = &avail_disks[*i];
+            (disk.to_string(), Some(disk.clone()))
+        }));
 
         let mut disk_form = FormView::new();
         for (i, _) in avail_disks.iter().enumerate() {
@@ -492,25 +940,65 @@ impl<T: View> MultiDiskOptionsView<T> {
                 SelectView::new()
                     .popup()
                     .with_all(selectable_disks.clone())
-                    .selected(selected_disks[i]),
+                    .selected(self.view_id_from_selection(selected_disks[i]))
+                    .on_select(|siv, _| AdvancedBootdiskOptionsView::update_usable_space(siv)),
             );
         }
 
-        let mut disk_select_view = LinearLayout::vertical().child(ScrollView::new(
-            disk_form.with_name(Self::DISK_FORM_VIEW_ID),
-        ));
+        let mut disk_select_view = LinearLayout::vertical()
+            .child(ScrollView::new(
+                disk_form.with_name(Self::DISK_FORM_VIEW_ID),
+            ))
+            .child(PaddedView::lrtb(
+                0,
+                0,
+                1,
+                0,
+                TextView::new("").with_name(USABLE_SPACE_VIEW_ID),
+            ));
 
         if avail_disks.len() > 3 {
-            let do_not_use_index = selectable_disks.len() - 1;
+            let do_not_use_index = self.view_id_from_selection(avail_disks.len());
             let deselect_all_button = Button::new("Deselect all", move |siv| {
                 siv.call_on_name(Self::DISK_FORM_VIEW_ID, |view: &mut FormView| {
                     view.call_on_childs(&|v: &mut SelectView<Option<Disk>>| {
-                        // As there is no .on_select() callback defined on the
-                        // SelectView's, the returned callback here can be safely
-                        // ignored.
+                        // The .on_select() callbacks only update the usable space estimate,
+                        // which is done once for all slots below instead.
                         v.set_selection(do_not_use_index);
                     });
                 });
+                AdvancedBootdiskOptionsView::update_usable_space(siv);
+            });
+
+            let disks = avail_disks.clone();
+            let disk_order = self.disk_order.clone();
+            let select_same_size_button = Button::new("Select same size", move |siv| {
+                siv.call_on_name(Self::DISK_FORM_VIEW_ID, |view: &mut FormView| {
+                    // The disk of the first used slot serves as reference
+                    let Some(reference) = (0..view.len())
+                        .find_map(|i| view.get_value::<SelectView<Option<Disk>>, _>(i).flatten())
+                    else {
+                        return;
+                    };
+
+                    // Allow for small differences between disks of the same nominal size
+                    let size = reference.size_bytes();
+                    let mut matching = disks_matching_size(&disks, size, size / 100);
+                    sort_disks_by_size(&mut matching);
+
+                    for i in 0..view.len() {
+                        // Same as Self::view_id_from_selection(), unused slots come first
+                        let view_id = matching
+                            .get(i)
+                            .and_then(|disk| disks.iter().position(|d| d.path == disk.path))
+                            .and_then(|index| disk_order.iter().position(|i| *i == index))
+                            .map_or(0, |pos| pos + 1);
+                        if let Some(select) = view.get_child_mut::<SelectView<Option<Disk>>>(i) {
+                            select.set_selection(view_id);
+                        }
+                    }
+                });
+                AdvancedBootdiskOptionsView::update_usable_space(siv);
             });
 
             disk_select_view.add_child(PaddedView::lrtb(
@@ -520,6 +1008,8 @@ impl<T: View> MultiDiskOptionsView<T> {
                 0,
                 LinearLayout::horizontal()
                     .child(DummyView.full_width())
+                    .child(select_same_size_button)
+                    .child(DummyView.fixed_width(2))
                     .child(deselect_all_button),
             ));
         }
@@ -589,7 +1079,12 @@ impl BtrfsBootdiskOptionsView {
                             .unwrap_or_default(),
                     ),
             )
-            .child("hdsize", DiskSizeEditView::new().content(options.disk_size));
+            .child(
+                "hdsize",
+                DiskSizeEditView::new()
+                    .content(options.disk_size)
+                    .max_value(largest_disk_size(&runinfo.disks)),
+            );
 
         let view = MultiDiskOptionsView::new(&runinfo.disks, &options.selected_disks, inner)
             .top_panel(TextView::new("Btrfs integration is a technology preview!").center());
@@ -656,7 +1151,12 @@ impl ZfsBootdiskOptionsView {
         };
 
         let inner = FormView::new()
-            .child("ashift", IntegerEditView::new().content(options.ashift))
+            .child(
+                "ashift",
+                IntegerEditView::new()
+                    .content(options.ashift)
+                    .max_value(*ZFS_ASHIFT_RANGE.end()),
+            )
             .child(
                 "compress",
                 SelectView::new()
@@ -683,10 +1183,71 @@ impl ZfsBootdiskOptionsView {
             )
             .child(
                 "copies",
-                IntegerEditView::new().content(options.copies).max_value(3),
+                IntegerEditView::new()
+                    .content(options.copies)
+                    .max_value(*ZFS_COPIES_RANGE.end()),
             )
             .child("ARC max size", arc_max_view)
-            .child("hdsize", DiskSizeEditView::new().content(options.disk_size));
+            .child(
+                "hdsize",
+                DiskSizeEditView::new()
+                    .content(options.disk_size)
+                    .max_value(largest_disk_size(&runinfo.disks)),
+            )
+            // Swap is opt-in, but pre-fill a sensible size for when it gets enabled
+            .child(
+                "swap zvol",
+                Checkbox::new().with_checked(options.swap_size > 0.),
+            )
+            .child(
+                "swap size",
+                DiskSizeEditView::new().content(if options.swap_size > 0. {
+                    options.swap_size
+                } else {
+                    options.suggested_swap_size(runinfo.total_memory)
+                }),
+            )
+            // Only used for dRAID setups, left empty to use the ZFS default
+            .child("dRAID data disks", {
+                let view = IntegerEditView::new().placeholder(ZFS_DRAID_DEFAULT_DATA);
+                match options.draid_data {
+                    Some(data) => view.content(data),
+                    None => view,
+                }
+            })
+            .child(
+                "dRAID spares",
+                IntegerEditView::new().content(options.draid_spares),
+            );
+
+        let (algorithm, passphrase) = options
+            .encryption
+            .as_ref()
+            .map(|e| (e.algorithm, e.passphrase.clone()))
+            .unwrap_or_default();
+
+        let inner = inner
+            .child(
+                "encryption",
+                SelectView::new()
+                    .popup()
+                    .with_all(ZFS_ENCRYPTION_OPTIONS.iter().map(|o| (o.to_string(), *o)))
+                    .selected(
+                        ZFS_ENCRYPTION_OPTIONS
+                            .iter()
+                            .position(|o| *o == algorithm)
+                            .unwrap_or_default(),
+                    ),
+            )
+            // Leaving the passphrase empty disables encryption
+            .child(
+                "encryption passphrase",
+                EditView::new().secret().content(passphrase.clone()),
+            )
+            .child(
+                "confirm passphrase",
+                EditView::new().secret().content(passphrase),
+            );
 
         let view = MultiDiskOptionsView::new(&runinfo.disks, &options.selected_disks, inner)
             .top_panel(TextView::new(
@@ -704,7 +1265,14 @@ impl ZfsBootdiskOptionsView {
         )
     }
 
-    fn get_values(&mut self) -> Option<(Vec<Disk>, ZfsBootdiskOptions)> {
+    fn get_values(&mut self) -> Result<(Vec<Disk>, ZfsBootdiskOptions), String> {
+        self.get_values_inner()
+            .ok_or("Failed to retrieve advanced bootdisk options".to_owned())?
+    }
+
+    /// Returns `None` if any of the fields cannot be retrieved, or an error if the encryption
+    /// passphrases do not match.
+    fn get_values_inner(&mut self) -> Option<Result<(Vec<Disk>, ZfsBootdiskOptions), String>> {
         let (disks, selected_disks) = self.view.get_disks_and_selection()?;
         let view = self.view.get_options_view()?;
 
@@ -713,6 +1281,23 @@ impl ZfsBootdiskOptionsView {
         let checksum = view.get_value::<SelectView<_>, _>(2)?;
         let copies = view.get_value::<IntegerEditView, _>(3)?;
         let disk_size = view.get_value::<DiskSizeEditView, _>(5)?;
+        let swap_size = if view.get_value::<Checkbox, _>(6)? {
+            view.get_value::<DiskSizeEditView, _>(7)?
+        } else {
+            0.
+        };
+        let draid_data = view
+            .get_child::<IntegerEditView>(8)?
+            .get_content_maybe()
+            .transpose()
+            .ok()?;
+        let draid_spares = view.get_value::<IntegerEditView, _>(9)?;
+        let algorithm = view.get_value::<SelectView<_>, _>(10)?;
+        let passphrase = view.get_value::<EditView, _>(11)?;
+
+        if passphrase != view.get_value::<EditView, _>(12)? {
+            return Some(Err("Encryption passphrases do not match".to_owned()));
+        }
 
         // If a value is set, return that and clamp it to at least [`ZFS_ARC_MIN_SIZE_MIB`].
         //
@@ -724,7 +1309,7 @@ impl ZfsBootdiskOptionsView {
             .map_or(Ok(0), |v| v.map(|v| v.max(ZFS_ARC_MIN_SIZE_MIB)))
             .unwrap_or(0);
 
-        Some((
+        Some(Ok((
             disks,
             ZfsBootdiskOptions {
                 ashift,
@@ -734,8 +1319,15 @@ impl ZfsBootdiskOptionsView {
                 arc_max,
                 disk_size,
                 selected_disks,
+                draid_data,
+                draid_spares,
+                swap_size,
+                encryption: (!passphrase.is_empty()).then_some(ZfsEncryption {
+                    algorithm,
+                    passphrase,
+                }),
             },
-        ))
+        )))
     }
 }
 
@@ -743,11 +1335,19 @@ impl ViewWrapper for ZfsBootdiskOptionsView {
     cursive::wrap_impl!(self.view: MultiDiskOptionsView<FormView>);
 }
 
+/// Upper bound for the `hdsize` of RAID setups, as the actual disk selection is only known once
+/// the dialog is submitted.
+fn largest_disk_size(disks: &[Disk]) -> f64 {
+    disks.iter().map(|disk| disk.size).fold(0., f64::max)
+}
+
 fn advanced_options_view(
     runinfo: &RuntimeInfo,
     options_ref: BootdiskOptionsRef,
     product_conf: ProductConfig,
 ) -> impl View {
+    let product = product_conf.product;
+
     Dialog::around(AdvancedBootdiskOptionsView::new(
         runinfo,
         options_ref.clone(),
@@ -776,10 +1376,8 @@ fn advanced_options_view(
                 }
             };
 
-            if let Err(duplicate) = check_for_duplicate_disks(&options.disks) {
-                siv.add_layer(Dialog::info(format!(
-                    "Cannot select same disk twice: {duplicate}"
-                )));
+            if let Err(err) = options.validate(product) {
+                siv.add_layer(Dialog::info(err.to_string()));
                 return;
             }
 
@@ -795,14 +1393,20 @@ fn advanced_options_view(
 ///
 /// # Arguments
 ///
-/// * `avail_disks` - Disks that should be shown in the select view
+/// * `runinfo` - Runtime information, providing the disks shown in the select view and the
+///   installed memory used for computing the default swap size
 /// * `options_ref` - [`BootdiskOptionsRef`] where advanced disk options should be saved to
 /// * `selected_disk` - Optional, specifies which disk should be pre-selected
 fn target_bootdisk_selectview(
-    avail_disks: &[Disk],
+    runinfo: &RuntimeInfo,
     options_ref: BootdiskOptionsRef,
     selected_disk: &Disk,
 ) -> SelectView<Disk> {
+    let avail_disks = disk_order_by_kind(&runinfo.disks)
+        .into_iter()
+        .map(|i| &runinfo.disks[i])
+        .collect::<Vec<&Disk>>();
+    let total_memory = runinfo.total_memory;
     let selected_disk_pos = avail_disks
         .iter()
         .position(|d| d.index == selected_disk.index)
@@ -810,12 +1414,13 @@ fn target_bootdisk_selectview(
 
     SelectView::new()
         .popup()
-        .with_all(avail_disks.iter().map(|d| (d.to_string(), d.clone())))
+        .with_all(avail_disks.iter().map(|d| (d.to_string(), (*d).clone())))
         .selected(selected_disk_pos)
         .on_submit(move |_, disk| {
             let mut options = options_ref.lock().unwrap();
             options.disks = vec![disk.clone()];
-            options.advanced =
-                AdvancedBootdiskOptions::Lvm(LvmBootdiskOptions::defaults_from(disk));
+            options.advanced = AdvancedBootdiskOptions::Lvm(
+                LvmBootdiskOptions::defaults_from_with_memory(disk, total_memory),
+            );
         })
 }
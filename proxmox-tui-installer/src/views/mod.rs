@@ -4,11 +4,14 @@ use cursive::{
     event::{Event, EventResult},
     theme::BaseColor,
     view::{Resizable, ViewWrapper},
-    views::{EditView, LinearLayout, NamedView, ResizedView, SelectView, TextView},
+    views::{Checkbox, EditView, LinearLayout, NamedView, ResizedView, SelectView, TextView},
     Printer, Rect, Vec2, View,
 };
 
-use proxmox_installer_common::utils::CidrAddress;
+use proxmox_installer_common::{
+    options::{LvmSize, LvmSizeParseError},
+    utils::CidrAddress,
+};
 
 mod bootdisk;
 pub use bootdisk::*;
@@ -182,7 +185,7 @@ impl<T: Copy + ToString + FromStr + PartialOrd> NumericEditView<T> {
     ///
     /// # Arguments
     /// * `content` - New, stringified content for the inner [`EditView`]. Must be a valid value
-    ///               according to the container type `T`.
+    ///   according to the container type `T`.
     fn content_inner(mut self, content: &str) -> Self {
         let mut inner = EditView::new();
         std::mem::swap(self.inner_mut(), &mut inner);
@@ -284,9 +287,10 @@ impl IntegerEditView {
     }
 }
 
+/// Edit view for disk sizes in GB, accepting at most two decimal places. Anything but digits and
+/// a single decimal point is rejected while typing.
 pub struct DiskSizeEditView {
     view: LinearLayout,
-    allow_empty: bool,
 }
 
 impl DiskSizeEditView {
@@ -295,21 +299,7 @@ impl DiskSizeEditView {
             .child(FloatEditView::new().full_width())
             .child(TextView::new(" GB"));
 
-        Self {
-            view,
-            allow_empty: false,
-        }
-    }
-
-    pub fn new_emptyable() -> Self {
-        let view = LinearLayout::horizontal()
-            .child(FloatEditView::new().allow_empty(true).full_width())
-            .child(TextView::new(" GB"));
-
-        Self {
-            view,
-            allow_empty: true,
-        }
+        Self { view }
     }
 
     pub fn content(mut self, content: f64) -> Self {
@@ -320,15 +310,16 @@ impl DiskSizeEditView {
         self
     }
 
-    pub fn content_maybe(self, content: Option<f64>) -> Self {
-        if let Some(value) = content {
-            self.content(value)
-        } else {
-            self
-        }
-    }
-
+    /// Sets the upper bound for the size, such that larger values cannot be entered at all.
+    ///
+    /// The bound is rounded up to the displayed precision of two decimal places, as the
+    /// pre-filled content would otherwise already be out of bounds if it got rounded up itself.
+    ///
+    /// # Arguments
+    /// * `max` - Maximum size in GB, usually the size of the (largest) disk.
     pub fn max_value(mut self, max: f64) -> Self {
+        let max = (max * 100.).ceil() / 100.;
+
         if let Some(view) = self
             .view
             .get_child_mut(0)
@@ -344,13 +335,7 @@ impl DiskSizeEditView {
         self.with_view(|v| {
             v.get_child(0)?
                 .downcast_ref::<ResizedView<FloatEditView>>()?
-                .with_view(|v| {
-                    if self.allow_empty {
-                        v.get_content_maybe().and_then(Result::ok)
-                    } else {
-                        v.get_content().ok()
-                    }
-                })
+                .with_view(|v| v.get_content().ok())
                 .flatten()
         })
         .flatten()
@@ -361,6 +346,78 @@ impl ViewWrapper for DiskSizeEditView {
     cursive::wrap_impl!(self.view: LinearLayout);
 }
 
+/// Edit view for optional LVM sizes, accepting either an absolute size in GB or a percentage of
+/// the total size, see [`LvmSize`].
+pub struct LvmSizeEditView {
+    view: LinearLayout,
+}
+
+impl LvmSizeEditView {
+    pub fn new() -> Self {
+        let view = LinearLayout::horizontal()
+            .child(EditView::new().full_width())
+            .child(TextView::new(" GB or %"));
+
+        Self { view }
+    }
+
+    pub fn content_maybe(mut self, content: Option<LvmSize>) -> Self {
+        let content = match content {
+            Some(LvmSize::Absolute(size)) => format!("{size:.2}"),
+            Some(size @ LvmSize::Percent(_)) => size.to_string(),
+            None => return self,
+        };
+
+        self.inner_mut().set_content(content);
+        self
+    }
+
+    /// Returns the current value of the view, or [`None`] if the view is currently empty.
+    pub fn get_content(&self) -> Result<Option<LvmSize>, LvmSizeParseError> {
+        let content = self.inner().get_content();
+
+        if content.is_empty() {
+            Ok(None)
+        } else {
+            content.parse().map(Some)
+        }
+    }
+
+    /// Provides an immutable reference to the inner [`EditView`].
+    fn inner(&self) -> &EditView {
+        // Safety: Invariant; first child must always exist and be a `EditView`
+        self.view
+            .get_child(0)
+            .unwrap()
+            .downcast_ref::<ResizedView<EditView>>()
+            .unwrap()
+            .get_inner()
+    }
+
+    /// Provides a mutable reference to the inner [`EditView`].
+    fn inner_mut(&mut self) -> &mut EditView {
+        // Safety: Invariant; first child must always exist and be a `EditView`
+        self.view
+            .get_child_mut(0)
+            .unwrap()
+            .downcast_mut::<ResizedView<EditView>>()
+            .unwrap()
+            .get_inner_mut()
+    }
+}
+
+impl ViewWrapper for LvmSizeEditView {
+    cursive::wrap_impl!(self.view: LinearLayout);
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            // Only allow characters which can make up a size or percentage
+            Event::Char(c) if !c.is_numeric() && c != '.' && c != '%' => EventResult::consumed(),
+            _ => self.view.on_event(event),
+        }
+    }
+}
+
 pub trait FormViewGetValue<R> {
     fn get_value(&self) -> Option<R>;
 }
@@ -371,6 +428,12 @@ impl FormViewGetValue<String> for EditView {
     }
 }
 
+impl FormViewGetValue<bool> for Checkbox {
+    fn get_value(&self) -> Option<bool> {
+        Some(self.is_checked())
+    }
+}
+
 impl<T: 'static + Clone + Send + Sync> FormViewGetValue<T> for SelectView<T> {
     fn get_value(&self) -> Option<T> {
         self.selection().map(|v| (*v).clone())
@@ -486,6 +549,20 @@ impl FormView {
         }
     }
 
+    /// Moves the focus to the child at the given index, e.g. to point the user to an invalid
+    /// value.
+    pub fn focus_child(&mut self, index: usize) {
+        let _ = self.view.set_focus_index(1); // ignore errors
+
+        if let Some(parent) = self
+            .view
+            .get_child_mut(1)
+            .and_then(|v| v.downcast_mut::<ResizedView<LinearLayout>>())
+        {
+            let _ = parent.get_inner_mut().set_focus_index(index); // ignore errors
+        }
+    }
+
     pub fn len(&self) -> usize {
         self.view
             .get_child(1)
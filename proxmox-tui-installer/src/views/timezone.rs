@@ -7,7 +7,7 @@ use cursive::{
 use super::FormView;
 use crate::{system, InstallerState};
 use proxmox_installer_common::{
-    options::TimezoneOptions,
+    options::{KeyboardLayout, TimezoneOptions},
     setup::{KeyboardMapping, LocaleInfo},
 };
 
@@ -37,36 +37,34 @@ impl TimezoneOptionsView {
             .with_all(countries.clone())
             .selected(country_selection_pos)
             .on_submit({
-                let cczones = locales.cczones.clone();
+                let locales = locales.clone();
                 move |siv: &mut Cursive, selected: &String| {
-                    siv.call_on_name("timezone-options-tz", {
-                        let cczones = cczones.clone();
-                        move |view: &mut SelectView| {
-                            *view =
-                                Self::timezone_selectview(cczones.get(selected).unwrap_or(&vec![]));
-                        }
+                    let defaults = TimezoneOptions::for_country(selected, &locales);
+                    let zones = locales.cczones.get(selected).map_or(&[][..], Vec::as_slice);
+
+                    siv.call_on_name("timezone-options-tz", |view: &mut SelectView| {
+                        *view = Self::timezone_selectview(zones, Some(&defaults.timezone));
                     });
                 }
             });
 
-        let mut kb_layouts = locales
-            .kmap
-            .clone()
-            .into_values()
-            .map(|l| (l.name.clone(), l))
+        let mut kb_layouts = KeyboardLayout::ALL
+            .iter()
+            .filter_map(|layout| locales.kmap.get(layout.as_str()))
+            .map(|l| (l.name.clone(), l.clone()))
             .collect::<Vec<(String, KeyboardMapping)>>();
         kb_layouts.sort();
 
         let kb_layout_selected_pos = kb_layouts
             .iter()
-            .position(|l| l.1.id == options.kb_layout)
+            .position(|l| l.1.id == options.kb_layout_str())
             .unwrap_or_default();
 
         let view = FormView::new()
             .child("Country", country_selectview)
             .child(
                 "Timezone",
-                Self::timezone_selectview(timezones.unwrap_or(&vec![]))
+                Self::timezone_selectview(timezones.unwrap_or(&vec![]), Some(&options.timezone))
                     .with_name("timezone-options-tz"),
             )
             .child(
@@ -115,17 +113,24 @@ impl TimezoneOptionsView {
         Ok(TimezoneOptions {
             country,
             timezone,
-            kb_layout: kmap.id,
+            kb_layout: kmap.id.parse().map_err(|err| format!("{err}"))?,
         })
     }
 
-    fn timezone_selectview(zones: &[String]) -> SelectView {
+    fn timezone_selectview(zones: &[String], selected: Option<&str>) -> SelectView {
         let mut zones = zones.to_owned();
         zones.sort();
         // Ensure UTC is always last
         zones.push("UTC".to_string());
 
-        SelectView::new().popup().with_all_str(zones)
+        let selected_pos = selected
+            .and_then(|tz| zones.iter().position(|z| z == tz))
+            .unwrap_or_default();
+
+        SelectView::new()
+            .popup()
+            .with_all_str(zones)
+            .selected(selected_pos)
     }
 }
 
@@ -0,0 +1,35 @@
+#!/usr/bin/env perl
+
+use strict;
+use warnings;
+
+use Test::More;
+
+use Proxmox::Install::Config;
+
+my $cfg = {
+    filesys => 'ext4',
+    root_password => { plain => 's3cr3t-password' },
+    lvm_encryption => { passphrase => 's3cr3t-passphrase', tpm_unlock => 1 },
+    zfs_opts => { ashift => 12, encryption => { algorithm => 'aes-256-gcm', passphrase => 'zfs-s3cr3t' } },
+};
+
+my $redacted = Proxmox::Install::Config::redact_secrets($cfg);
+is_deeply($redacted, {
+    filesys => 'ext4',
+    root_password => { plain => '<redacted>' },
+    lvm_encryption => { passphrase => '<redacted>', tpm_unlock => 1 },
+    zfs_opts => { ashift => 12, encryption => { algorithm => 'aes-256-gcm', passphrase => '<redacted>' } },
+}, 'secrets are redacted');
+is($cfg->{root_password}->{plain}, 's3cr3t-password', 'original root password is untouched');
+is($cfg->{lvm_encryption}->{passphrase}, 's3cr3t-passphrase', 'original passphrase is untouched');
+is($cfg->{zfs_opts}->{encryption}->{passphrase}, 'zfs-s3cr3t', 'original zfs passphrase is untouched');
+
+$cfg = {
+    root_password => { hashed => '$5$rounds=5000$salt$hash' },
+    lvm_encryption => undef,
+    zfs_opts => { ashift => 12, encryption => undef },
+};
+is_deeply(Proxmox::Install::Config::redact_secrets($cfg), $cfg, 'hashed passwords are kept');
+
+done_testing();
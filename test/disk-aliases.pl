@@ -0,0 +1,109 @@
+#!/usr/bin/env perl
+
+use strict;
+use warnings;
+
+use Test::More;
+
+use Proxmox::Sys::Block;
+
+# builds a disk entry as returned by hd_list(), without aliases
+my $count = 0;
+sub disk {
+    my ($name, $dev_path) = @_;
+    return [
+	$count++, $dev_path // "/dev/$name", 1000, 'MODEL', 512, "/sys/block/$name", '', '', '', 4096,
+	"/dev/disk/by-id/wwn-$name", { partitions => 0, signatures => [] },
+    ];
+}
+
+# returns a reduced view of the deduplicated disks, only containing the index, path and aliases
+sub dedup {
+    my ($disks, $devices) = @_;
+    my $res = Proxmox::Sys::Block::dedup_disk_aliases($disks, $devices);
+    return [ map { [ $_->[0], $_->[1], $_->[12] ] } $res->@* ];
+}
+
+{
+    $count = 0;
+    my $disks = [ disk('sda'), disk('sdb'), disk('nvme0n1') ];
+    my $devices = {
+	sda => { props => { ID_BUS => 'ata', ID_WWN => '0x5002538e40a1b2c3' } },
+	sdb => { props => { ID_BUS => 'ata', ID_SERIAL => 'Samsung_SSD_870_S1' } },
+	nvme0n1 => { props => { ID_WWN => 'eui.0025388b91b2c3d4' } },
+    };
+
+    is_deeply(dedup($disks, $devices), [
+	[ 0, '/dev/sda', [] ],
+	[ 1, '/dev/sdb', [] ],
+	[ 2, '/dev/nvme0n1', [] ],
+    ], 'distinct disks are kept as-is');
+}
+
+{
+    $count = 0;
+    my $disks = [
+	disk('dm-0', '/dev/mapper/mpatha'), disk('sda'), disk('sdc'), disk('sdd'), disk('sde'),
+    ];
+    my $devices = {
+	'dm-0' => {
+	    props => { DM_UUID => 'mpath-36001405abcdef', DM_NAME => 'mpatha' },
+	    slaves => [ 'sdc', 'sdd' ],
+	},
+	sda => { props => { ID_BUS => 'ata', ID_WWN => '0x5002538e40a1b2c3' } },
+	sdc => { props => { ID_BUS => 'scsi', ID_WWN_WITH_EXTENSION => '0x6001405abcdef' } },
+	sdd => { props => { ID_BUS => 'scsi', ID_WWN_WITH_EXTENSION => '0x6001405abcdef' } },
+	sde => { props => { ID_BUS => 'scsi', ID_WWN_WITH_EXTENSION => '0x6001405abcdef' } },
+    };
+
+    is_deeply(dedup($disks, $devices), [
+	[ 0, '/dev/mapper/mpatha', [ '/dev/sdc', '/dev/sdd', '/dev/sde' ] ],
+	[ 1, '/dev/sda', [] ],
+    ], 'multipath device is preferred over its paths');
+}
+
+{
+    $count = 0;
+    my $disks = [ disk('sdc'), disk('sdd'), disk('sde'), disk('sdf') ];
+    my $devices = {
+	sdc => { props => { ID_BUS => 'scsi', ID_WWN => '0x6001405abcdef' } },
+	sdd => { props => { ID_BUS => 'scsi', ID_SERIAL => '36001405fedcba' } },
+	sde => { props => { ID_BUS => 'scsi', ID_WWN => '0x6001405abcdef' } },
+	sdf => { props => { ID_BUS => 'scsi', ID_SERIAL => '36001405fedcba' } },
+    };
+
+    is_deeply(dedup($disks, $devices), [
+	[ 0, '/dev/sdc', [ '/dev/sde' ] ],
+	[ 1, '/dev/sdd', [ '/dev/sdf' ] ],
+    ], 'paths without multipath device are grouped by WWN or SCSI serial');
+}
+
+{
+    $count = 0;
+    my $disks = [ disk('nvme0n1'), disk('nvme0n2'), disk('sda'), disk('sdb') ];
+    my $devices = {
+	nvme0n1 => { props => { ID_SERIAL => 'S64ANS0R123456' } },
+	nvme0n2 => { props => { ID_SERIAL => 'S64ANS0R123456' } },
+	sda => { props => { ID_BUS => 'usb', ID_SERIAL => '000000000000' } },
+	sdb => { props => { ID_BUS => 'usb', ID_SERIAL => '000000000000' } },
+    };
+
+    is_deeply(dedup($disks, $devices), [
+	[ 0, '/dev/nvme0n1', [] ],
+	[ 1, '/dev/nvme0n2', [] ],
+	[ 2, '/dev/sda', [] ],
+	[ 3, '/dev/sdb', [] ],
+    ], 'shared serials outside of SCSI do not group disks');
+}
+
+{
+    $count = 0;
+    my $disks = [ disk('sda'), disk('sdb') ];
+
+    is_deeply(dedup($disks, {}), [
+	[ 0, '/dev/sda', [] ],
+	[ 1, '/dev/sdb', [] ],
+    ], 'missing udev properties are handled');
+}
+
+done_testing();
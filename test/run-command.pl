@@ -6,7 +6,7 @@ use warnings;
 use File::Temp;
 use Test::More;
 
-use Proxmox::Sys::Command qw(run_command CMD_FINISHED);
+use Proxmox::Sys::Command qw(run_command run_command_secret CMD_FINISHED);
 use Proxmox::Sys::File qw(file_read_all);
 use Proxmox::UI;
 
@@ -38,8 +38,21 @@ alarm($prev);
 
 is($@, "timed out!\n", 'SIGALRM interaction');
 
+# The input must never be logged for secret-carrying commands, regardless of the command name.
+# Mimics the TPM2 enrollment of the LUKS key, with bash consuming the input instead.
+my $passphrase = 'correct horse battery staple';
+my $cryptenroll = [
+    'bash', '-c', 'cat >/dev/null', 'systemd-cryptenroll', '--tpm2-device=auto',
+    '--unlock-key-file=/dev/stdin', '/dev/sda3',
+];
+is(run_command_secret($cryptenroll, undef, $passphrase), '', 'secret input - basic usage');
+is(run_command('cat >/dev/null', undef, 'not a secret'), '', 'plain input - basic usage');
+
 # Check the log for errors/warnings
 my $log = file_read_all($log_file->filename);
+like($log, qr/systemd-cryptenroll/, 'secret input - command logged');
+unlike($log, qr/\Q$passphrase\E/, 'secret input - input not logged');
+like($log, qr/cat >\/dev\/null <<EOD\nnot a secret\nEOD/, 'plain input - input logged');
 ok($log !~ m/(WARN|ERROR): /, 'no warnings or errors logged');
 print $log if $log =~ m/(WARN|ERROR): /;
 